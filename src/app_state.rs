@@ -1,52 +1,135 @@
 use crate::{
+    command::{self, Command, History},
+    file_picker::FilePicker,
+    file_tree::FileTree,
+    filter::Filter,
+    inspector::Inspector,
+    jump_list::{Jump, JumpList, TupleRef},
+    keymap::{Context, Keymap},
     list::{self, List},
-    parsers::{self, MetaInfo, Relation},
-    source_view::SourceView,
+    parsers::{self, Located, MetaInfo, Position, Relation},
+    session::Session,
+    source_view::{Search, SourceView},
+    tuple_table::TupleTable,
 };
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Copy)]
 pub enum AppFocus {
     Relations,
     Tuples,
     Source,
     FilePicker,
-    LinePicker,
+    CommandLine,
+    Problems,
+    FilterPrompt,
+    SearchPrompt,
+    Tree,
+    Help,
+    Inspector,
 }
 
 impl AppFocus {
-    pub fn next(&self) -> AppFocus {
+    /// The key bindings that apply
+    pub fn context(&self) -> Context {
         match self {
+            AppFocus::Relations => Context::Relations,
+            AppFocus::Tuples => Context::Tuples,
+            AppFocus::Source => Context::Source,
+            AppFocus::Tree => Context::Tree,
+            AppFocus::Problems => Context::Problems,
+            AppFocus::Help => Context::Help,
+            AppFocus::Inspector => Context::Inspector,
+            AppFocus::FilePicker
+            | AppFocus::CommandLine
+            | AppFocus::FilterPrompt
+            | AppFocus::SearchPrompt => Context::Prompt,
+        }
+    }
+
+    /// The next pane, `Tree` is only part of the cycle if `with_tree`
+    pub fn next(&self, with_tree: bool) -> AppFocus {
+        match self {
+            AppFocus::Tuples if with_tree => AppFocus::Tree,
             AppFocus::Tuples => AppFocus::Source,
+            AppFocus::Tree => AppFocus::Source,
             AppFocus::Source => AppFocus::Relations,
             AppFocus::Relations => AppFocus::Tuples,
-            _ => panic!("invalid call to next"),
+            // Cycling focus closes any popup
+            _ => AppFocus::Source,
         }
     }
 
-    pub fn prev(&self) -> AppFocus {
+    pub fn prev(&self, with_tree: bool) -> AppFocus {
         match self {
             AppFocus::Tuples => AppFocus::Relations,
+            AppFocus::Source if with_tree => AppFocus::Tree,
             AppFocus::Source => AppFocus::Tuples,
+            AppFocus::Tree => AppFocus::Tuples,
             AppFocus::Relations => AppFocus::Source,
-            _ => panic!("invalid call to next"),
+            _ => AppFocus::Source,
         }
     }
 }
 
+/// The cursor of the source view on `position`
+fn cursor_at(position: Position) -> (u16, u16) {
+    (position.col as u16, position.line.saturating_sub(1) as u16)
+}
+
+/// The position under the cursor of the source view, the inverse of `cursor_at`
+fn position_at(cursor: (u16, u16)) -> Position {
+    Position::new(cursor.1 as usize + 1, cursor.0 as usize)
+}
+
 pub struct AppState {
     pub metainfo: MetaInfo,
     pub tuples: List<parsers::Tuple>,
-    pub files: List<String>,
+    pub tuple_table: TupleTable,
+    /// Applied on top of the tuples of the selected relation
+    pub filter: Option<Filter>,
+    /// Number of tuples in the selected relation before filtering
+    pub tuples_total: usize,
+    pub file_picker: FilePicker,
+    pub file_tree: FileTree,
+    /// The directory tree pane is shown next to the source
+    pub show_tree: bool,
+    pub jumps: JumpList,
+    pub keymap: Keymap,
+    /// The focus the help was opened from, its bindings are listed first
+    pub help_for: AppFocus,
+    pub help_scroll: u16,
+    /// The focus the command line or inspector was opened from, to go back to
+    pub popup_from: AppFocus,
+    /// The nodes under the cursor when the inspector was opened
+    pub inspector: Option<Inspector>,
+    pub history: History,
+    /// Completions of the command line cycled through with tab, and the one shown
+    pub completions: Vec<String>,
+    pub completion: Option<usize>,
+    /// Problems encountered while loading, see `MetaInfo::problems`
+    pub problems: List<String>,
     pub relations: List<parsers::Relation>,
     pub should_quit: bool,
     pub focus: AppFocus,
     pub sv: SourceView,
-    pub current_nodes: Vec<usize>,
+    /// The nodes and row locations under the cursor, innermost first
+    pub current_nodes: Vec<Located>,
+    /// The tuples of `current_nodes`, looked up when they change rather than on every frame
+    pub current_tuples: Vec<parsers::Tuple>,
+    /// Index in `current_nodes` of the focused node, only its tuples are marked
+    pub node_depth: usize,
     pub input_buffer: String,
+    /// Shown in the prompt when its input was rejected
+    pub input_error: Option<String>,
+    /// The search prompt was opened with `\`
+    pub search_backwards: bool,
 }
 
 impl AppState {
-    pub fn new(metainfo: MetaInfo, files: List<String>) -> Self {
+    pub fn new(metainfo: MetaInfo, file_picker: FilePicker) -> Self {
+        let files = metainfo.source_files.keys().cloned().collect::<Vec<_>>();
+        let mut file_tree = FileTree::new(&files);
+        file_tree.set_relation(&metainfo, None);
         let mut relation_names = metainfo
             .analyses
             .iter()
@@ -57,75 +140,133 @@ impl AppState {
             .into_iter()
             .map(Relation::new)
             .collect::<Vec<_>>();
+        let problems = metainfo
+            .problems
+            .iter()
+            .map(|p| p.to_string())
+            .collect::<Vec<_>>();
 
         Self {
             metainfo,
             relations: List::new(relations),
             tuples: List::new(vec![]),
-            files,
+            tuple_table: TupleTable::new(),
+            filter: None,
+            tuples_total: 0,
+            file_picker,
+            file_tree,
+            show_tree: false,
+            jumps: JumpList::new(),
+            keymap: Keymap::default(),
+            help_for: AppFocus::Source,
+            help_scroll: 0,
+            popup_from: AppFocus::Source,
+            inspector: None,
+            history: History::new(),
+            completions: vec![],
+            completion: None,
+            problems: List::new(problems),
             should_quit: false,
             focus: AppFocus::Relations,
             sv: SourceView::new(),
             current_nodes: vec![],
+            current_tuples: vec![],
+            node_depth: 0,
             input_buffer: String::new(),
+            input_error: None,
+            search_backwards: false,
         }
     }
 
-    pub fn nodes_at(&self, row: usize, col: usize) -> Vec<usize> {
-        // Adjust for indexing
-        let row = row + 1;
+    pub fn nodes_under_cursor(&self) -> Vec<Located> {
+        let position = position_at(self.sv.get_cursor());
+        self.metainfo.nodes_at(&self.sv.name, position)
+    }
 
-        self.metainfo
-            .debug_locs
-            .iter()
-            .filter(|d| {
-                let h = &d.loc;
-                if d.loc.source_file == self.sv.name && (h.start_line <= row && row <= h.end_line) {
-                    if h.start_line == h.end_line {
-                        h.start_col <= col && col <= h.end_col
-                    } else if h.start_line == row {
-                        h.start_col <= col
-                    } else if h.end_line == row {
-                        col <= h.end_col
-                    } else {
-                        true
-                    }
-                } else {
-                    false
-                }
-            })
-            .map(|d| d.node_id)
-            .collect()
+    /// Focus the innermost node again when the cursor moves to other nodes
+    /// The underlined node is dropped when the nodes under the cursor change
+    fn set_current_nodes(&mut self, nodes: Vec<Located>) {
+        if nodes != self.current_nodes {
+            self.current_tuples = self.metainfo.get_analyses(&nodes);
+            self.current_nodes = nodes;
+            self.node_depth = 0;
+            self.sv.node = None;
+        }
+    }
+
+    pub fn focused_node(&self) -> Option<Located> {
+        self.current_nodes.get(self.node_depth).copied()
     }
 
     pub fn mark_nodes_under_cursor(&mut self) {
-        let (col, row) = self.sv.get_cursor();
-        self.current_nodes = self.nodes_at(row.into(), col.into());
+        let nodes = self.nodes_under_cursor();
+        self.set_current_nodes(nodes);
+        let focused = self.focused_node();
+        let metainfo = &self.metainfo;
         self.tuples
-            .mark(|d| d.nodes.iter().any(|n| self.current_nodes.contains(n)));
+            .mark(|d| focused.is_some_and(|f| metainfo.touches(d, f)));
     }
 
-    pub fn get_current_tuples(&self) -> Vec<parsers::Tuple> {
-        self.metainfo.get_analyses(&self.current_nodes)
+    /// Focus the node enclosing the focused one, or the one inside it if not `outwards`, and
+    /// underline its span
+    pub fn move_node_focus(&mut self, outwards: bool) {
+        self.node_depth = if outwards {
+            (self.node_depth + 1).min(self.current_nodes.len().saturating_sub(1))
+        } else {
+            self.node_depth.saturating_sub(1)
+        };
+        self.sv.node = self
+            .focused_node()
+            .and_then(|n| self.metainfo.get_span(n))
+            .cloned();
     }
 
     pub fn get_tuples_for_relation(&mut self, relation: &str) {
         let tuples = self.metainfo.get_tuples_for_relation(relation);
+        self.tuples_total = tuples.len();
         let mut l = vec![];
         for d in &tuples {
-            l.push(d.clone());
+            if self.filter.as_ref().is_none_or(|f| f.matches(d)) {
+                l.push(d.clone());
+            }
         }
         let tuples = list::List::new(l);
         self.tuples = tuples;
+        self.file_tree.set_relation(&self.metainfo, Some(relation));
+        self.tuple_table
+            .reset(&self.tuples, self.metainfo.get_columns(relation));
+    }
+
+    /// Set the filter of the tuples pane, an empty `source` removes it
+    pub fn set_filter(&mut self, source: &str) -> Result<(), String> {
+        self.filter = if source.trim().is_empty() {
+            None
+        } else {
+            Some(Filter::parse(source)?)
+        };
+
+        if let Some(relation) = self.relations.selected().map(|r| r.name.clone()) {
+            self.get_tuples_for_relation(&relation);
+        }
+
+        Ok(())
     }
 
     /// Load a file from the project
     pub fn load_file(&mut self, file: &str) {
         let sc = &self.metainfo.source_files[file];
+        self.open_source(sc.name.clone(), Some(sc.content.clone()));
+    }
 
+    fn open_source(&mut self, name: String, content: Option<String>) {
         let mut sv = SourceView::new();
-        sv.content = Some(sc.content.clone());
-        sv.name = file.to_owned();
+        // Keep searching for the same pattern in the new file
+        if let (Some(search), Some(content)) = (&self.sv.search, &content) {
+            sv.search = Search::new(&search.pattern, search.backwards, content).ok();
+        }
+        sv.name = name;
+        sv.content = content;
+        sv.tab_width = self.sv.tab_width;
 
         self.sv = sv;
     }
@@ -135,31 +276,431 @@ impl AppState {
             return;
         }
         let tuples = self.tuples.selected().unwrap();
-        let loc = tuples.current().unwrap();
+        // Nothing to show for a tuple where no node has a location
+        let Some(loc) = tuples.current() else {
+            self.sv.highlights = tuples.located().cloned().collect();
+            return;
+        };
 
         if self.sv.name != loc.source_file {
-            let mut sv = SourceView::new();
-            sv.name = loc.source_file.clone();
-            sv.content = self
+            let name = loc.source_file.clone();
+            let content = self
                 .metainfo
                 .source_files
-                .get(&sv.name)
+                .get(&name)
                 .map(|s| s.content.clone());
-            self.sv = sv;
+            self.open_source(name, content);
         }
 
-        let sv = &mut self.sv;
-        sv.highlights = tuples.locs.clone();
         self.scroll_into_view();
-        let (col, row) = self.sv.get_cursor();
-        self.current_nodes = self.nodes_at(row.into(), col.into());
+        let nodes = self.nodes_under_cursor();
+        self.set_current_nodes(nodes);
     }
 
     pub fn scroll_into_view(&mut self) {
-        let selected = &self.tuples.selected().unwrap();
-        self.sv.highlights = selected.locs.clone();
-        let current = selected.current().unwrap();
-        let target = ((current.start_col) as u16, (current.start_line - 1) as u16);
-        self.sv.move_to(target);
+        let Some(selected) = self.tuples.selected() else {
+            return;
+        };
+        self.sv.highlights = selected.located().cloned().collect();
+        self.sv.node = None;
+        let Some(current) = selected.current() else {
+            return;
+        };
+        self.sv.move_to(cursor_at(current.start()));
+    }
+
+    /// Where the cursor is and what is selected, to come back to later
+    pub fn current_jump(&mut self) -> Jump {
+        Jump {
+            file: self.sv.name.clone(),
+            cursor: self.sv.get_cursor(),
+            relation: self.relations.selected().map(|r| r.name.clone()),
+            tuple: self.tuples.selected().map(|t| TupleRef {
+                fields: t.fields.clone(),
+                loc: t.current_index(),
+            }),
+        }
+    }
+
+    /// Call before moving somewhere else, so that `ctrl-o` comes back here
+    pub fn record_jump(&mut self) {
+        let jump = self.current_jump();
+        self.jumps.record(jump);
+    }
+
+    pub fn jump_back(&mut self) {
+        let current = self.current_jump();
+        if let Some(jump) = self.jumps.back(current) {
+            self.restore_jump(jump);
+        }
+    }
+
+    pub fn jump_forward(&mut self) {
+        if let Some(jump) = self.jumps.forward() {
+            self.restore_jump(jump);
+        }
+    }
+
+    /// Move the session out, leaving an empty jump list behind. Only for when the state is done
+    /// with, at shutdown or when it is replaced on reload.
+    pub fn take_session(&mut self) -> Session {
+        Session {
+            position: self.current_jump(),
+            scroll: self.sv.get_scroll(),
+            filter: self.filter.as_ref().map(|f| f.source.clone()),
+            jumps: std::mem::replace(&mut self.jumps, JumpList::new()),
+        }
+    }
+
+    /// Go back to a saved session, a filter that no longer parses is dropped
+    pub fn restore_session(&mut self, session: Session) {
+        if let Some(filter) = &session.filter {
+            self.filter = Filter::parse(filter).ok();
+        }
+        self.restore_jump(session.position);
+        self.sv.set_scroll(session.scroll);
+        self.jumps = session.jumps;
+        if self.focus == AppFocus::Relations && self.tuples.selected().is_some() {
+            self.focus = AppFocus::Tuples;
+        }
+    }
+
+    /// Select the relation called `name`, or none, and load its tuples
+    fn select_relation(&mut self, name: Option<&str>) {
+        if let Some(s) = self.relations.selected() {
+            s.unmark();
+        }
+        let index = name.and_then(|name| self.relations.items.iter().position(|r| r.name == name));
+        self.relations.state.select(index);
+        self.relations.confirm();
+        if let Some(s) = self.relations.selected() {
+            s.mark();
+        }
+        match name {
+            Some(name) => self.get_tuples_for_relation(name),
+            None => self.tuples = List::new(vec![]),
+        }
+    }
+
+    fn restore_jump(&mut self, jump: Jump) {
+        let relation = self.relations.selected().map(|r| r.name.clone());
+        if jump.relation != relation {
+            self.select_relation(jump.relation.as_deref());
+        }
+
+        if let Some(s) = self.tuples.selected() {
+            s.unset();
+        }
+        // The tuple may be filtered out by now
+        let index = jump.tuple.as_ref().and_then(|t| {
+            self.tuples
+                .items
+                .iter()
+                .position(|tuple| tuple.fields == t.fields)
+        });
+        match index {
+            Some(index) => {
+                self.tuples.state.select(Some(index));
+                self.tuples.confirm();
+            }
+            None => self.tuples.deselect(),
+        }
+        if let (Some(s), Some(t)) = (self.tuples.selected(), &jump.tuple) {
+            s.set();
+            if let Some(loc) = t.loc {
+                s.select_index(loc);
+            }
+        }
+
+        if self.sv.name != jump.file && self.metainfo.source_files.contains_key(&jump.file) {
+            self.load_file(&jump.file);
+        }
+        self.sv.highlights = match self.tuples.selected() {
+            Some(s) => s.located().cloned().collect(),
+            None => vec![],
+        };
+        self.sv.node = None;
+        self.sv.move_to(jump.cursor);
+        self.mark_nodes_under_cursor();
+    }
+
+    /// Select `tuple` in the tuples pane with the span of `node` as its current location,
+    /// dropping the filter if it hides the tuple
+    pub fn show_tuple(&mut self, tuple: &parsers::Tuple, node: Located) {
+        self.record_jump();
+        let filtered = self.filter.as_ref().is_some_and(|f| !f.matches(tuple));
+        if filtered {
+            self.filter = None;
+        }
+        if filtered || self.relations.selected().map(|r| &r.name) != Some(&tuple.name) {
+            self.select_relation(Some(&tuple.name));
+        }
+
+        let index = match node {
+            Located::Node(id) => tuple.nodes.iter().position(|n| *n == id),
+            Located::Tuple(_, j) => Some(tuple.nodes.len() + j),
+        };
+        let loc = index.and_then(|i| tuple.locs[i].as_ref());
+        let (file, cursor) = match loc {
+            Some(loc) => (loc.source_file.clone(), cursor_at(loc.start())),
+            None => (self.sv.name.clone(), self.sv.get_cursor()),
+        };
+        self.restore_jump(Jump {
+            file,
+            cursor,
+            relation: Some(tuple.name.clone()),
+            tuple: Some(TupleRef {
+                fields: tuple.fields.clone(),
+                loc: index,
+            }),
+        });
+    }
+
+    /// Completions of the command line, see `command::complete`
+    pub fn complete_command(&self) -> Vec<String> {
+        command::complete(&self.input_buffer, |name| match name {
+            "rel" => self
+                .relations
+                .items
+                .iter()
+                .map(|r| r.name.clone())
+                .collect(),
+            "file" => {
+                let mut files = self
+                    .metainfo
+                    .source_files
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<_>>();
+                files.sort();
+                files
+            }
+            "node" => self
+                .metainfo
+                .node_ids()
+                .iter()
+                .map(|n| n.to_string())
+                .collect(),
+            _ => vec![],
+        })
+    }
+
+    /// Run a command from the command line, the focus is left on the pane it is about
+    pub fn run_command(&mut self, input: &str) -> Result<(), String> {
+        match Command::parse(input)? {
+            Command::Line(line, column) => {
+                let Some(content) = &self.sv.content else {
+                    return Err(String::from("no file is open"));
+                };
+                let lines = content.lines().count();
+                if line > lines {
+                    return Err(format!("the file has {} lines", lines));
+                }
+                self.record_jump();
+                self.sv
+                    .move_to(cursor_at(Position::new(line, column.unwrap_or(1))));
+                self.focus = AppFocus::Source;
+            }
+            Command::Node(id) => {
+                self.goto_node(id)?;
+                self.focus = AppFocus::Source;
+            }
+            Command::Relation(name) => {
+                if !self.relations.items.iter().any(|r| r.name == name) {
+                    return Err(format!("no relation named `{}`", name));
+                }
+                self.record_jump();
+                self.select_relation(Some(&name));
+                self.sv.highlights.clear();
+                self.focus = AppFocus::Tuples;
+            }
+            Command::File(name) => {
+                let file = self.find_file(&name)?;
+                self.record_jump();
+                self.load_file(&file);
+                self.focus = AppFocus::Source;
+            }
+            Command::Filter(source) => {
+                self.set_filter(&source)?;
+                self.focus = AppFocus::Tuples;
+            }
+            Command::Export(path) => self.export(&path)?,
+            Command::Reload => self.reload()?,
+        }
+        self.mark_nodes_under_cursor();
+
+        Ok(())
+    }
+
+    /// Move the cursor to the start of node `id`, opening its file, and underline its span
+    pub fn goto_node(&mut self, id: usize) -> Result<(), String> {
+        let Some(loc) = self.metainfo.get_loc(id).map(|d| d.loc.clone()) else {
+            return Err(if self.metainfo.has_node(id) {
+                format!("node {} is not in any loc file", id)
+            } else {
+                format!("no tuple mentions node {}", id)
+            });
+        };
+        if !self.metainfo.source_files.contains_key(&loc.source_file) {
+            return Err(format!(
+                "node {} is in {}, which could not be read",
+                id, loc.source_file
+            ));
+        }
+
+        self.record_jump();
+        if self.sv.name != loc.source_file {
+            self.load_file(&loc.source_file);
+        }
+        self.sv.move_to(cursor_at(loc.start()));
+        self.mark_nodes_under_cursor();
+        if let Some(depth) = self
+            .current_nodes
+            .iter()
+            .position(|&n| n == Located::Node(id))
+        {
+            self.node_depth = depth;
+        }
+        self.sv.node = Some(loc);
+
+        Ok(())
+    }
+
+    /// The file called `name`, or the only one ending with or else containing it
+    fn find_file(&self, name: &str) -> Result<String, String> {
+        let files = &self.metainfo.source_files;
+        if files.contains_key(name) {
+            return Ok(name.to_owned());
+        }
+
+        let suffix = format!("/{}", name);
+        let mut matches = files
+            .keys()
+            .filter(|f| f.ends_with(&suffix))
+            .collect::<Vec<_>>();
+        if matches.is_empty() {
+            matches = files.keys().filter(|f| f.contains(name)).collect();
+        }
+        match matches.as_slice() {
+            [file] => Ok((*file).clone()),
+            [] => Err(format!("no file matches `{}`", name)),
+            _ => Err(format!("`{}` matches {} files", name, matches.len())),
+        }
+    }
+
+    /// Write the tuples shown in the tuples pane to `path` as CSV, with a header if the
+    /// relation has a schema
+    fn export(&mut self, path: &str) -> Result<(), String> {
+        let Some(relation) = self.relations.selected().map(|r| r.name.clone()) else {
+            return Err(String::from("select a relation to export first"));
+        };
+        let error = |e: csv::Error| format!("could not write {}: {}", path, e);
+        let mut writer = csv::Writer::from_path(path).map_err(error)?;
+
+        // From the schema, so that a relation without tuples still gets a header
+        if let Some(columns) = self.metainfo.get_columns(&relation) {
+            writer
+                .write_record(columns.iter().map(|c| c.name.as_str()))
+                .map_err(error)?;
+        }
+        for tuple in &self.tuples.items {
+            let fields = tuple.fields.iter().map(|f| match f {
+                parsers::Field::Node(n) => n.to_string(),
+                parsers::Field::Value(v) => v.clone(),
+            });
+            writer.write_record(fields).map_err(error)?;
+        }
+        writer
+            .flush()
+            .map_err(|e| format!("could not write {}: {}", path, e))
+    }
+
+    /// Load the root again, keeping the position, filter and jumps
+    fn reload(&mut self) -> Result<(), String> {
+        let metainfo = MetaInfo::new(&self.metainfo.root)
+            .map_err(|e| format!("could not reload {}: {}", self.metainfo.root, e))?;
+        let files = metainfo.source_files.keys().cloned().collect::<Vec<_>>();
+        let mut state = AppState::new(metainfo, FilePicker::new(files));
+
+        let session = self.take_session();
+        state.keymap = std::mem::take(&mut self.keymap);
+        state.history = std::mem::replace(&mut self.history, History::new());
+        state.show_tree = self.show_tree;
+        state.focus = self.focus;
+        // Searched again once the file is open
+        state.sv.search = self.sv.search.take();
+        state.sv.tab_width = self.sv.tab_width;
+        state.restore_session(session);
+
+        *self = state;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_root;
+
+    #[test]
+    fn test_node_focus() {
+        let metainfo = MetaInfo::new("./example_data").unwrap();
+        let mut app = AppState::new(metainfo, FilePicker::new(vec![]));
+        app.run_command("rel Call").unwrap();
+        app.run_command("file test1.c").unwrap();
+        app.run_command("27:12").unwrap();
+        assert_eq!(
+            app.current_nodes,
+            vec![Located::Node(46), Located::Node(14)]
+        );
+        assert_eq!(app.tuples.marked, vec![0]);
+        assert_eq!(app.current_tuples.len(), 3);
+
+        app.move_node_focus(true);
+        app.move_node_focus(true);
+        app.mark_nodes_under_cursor();
+        assert_eq!(app.focused_node(), Some(Located::Node(14)));
+        assert_eq!(app.tuples.marked, vec![0, 1]);
+        assert_eq!(app.sv.node.as_ref().map(|l| l.start_col), Some(3));
+
+        app.move_node_focus(false);
+        app.mark_nodes_under_cursor();
+        assert_eq!(app.focused_node(), Some(Located::Node(46)));
+        assert!(app.sv.node.is_some());
+
+        // Other nodes start from the innermost one again, without an underline
+        app.run_command("27:15").unwrap();
+        assert_eq!(
+            app.current_nodes,
+            vec![Located::Node(47), Located::Node(14)]
+        );
+        assert_eq!(app.focused_node(), Some(Located::Node(47)));
+        assert!(app.sv.node.is_none());
+
+        app.run_command("node 14").unwrap();
+        assert_eq!(app.focused_node(), Some(Located::Node(14)));
+        assert_eq!(app.sv.node.as_ref().map(|l| l.start_col), Some(3));
+    }
+
+    #[test]
+    fn test_export_header() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[{"name": "A", "file": "A.csv", "columns": [{"name": "a"}, {"name": "b"}]}]"#,
+            ),
+            ("A.csv", "x,y\n"),
+        ]);
+        let metainfo = MetaInfo::new(root.to_str().unwrap()).unwrap();
+        let mut app = AppState::new(metainfo, FilePicker::new(vec![]));
+
+        let path = root.join("out.csv");
+        app.run_command("rel A").unwrap();
+        // No tuple is left to take the header from
+        app.run_command("filter z").unwrap();
+        assert!(app.tuples.items.is_empty());
+        app.run_command(&format!("export {}", path.display()))
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "a,b\n");
     }
 }
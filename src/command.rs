@@ -0,0 +1,203 @@
+/// A command typed after `:`
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    /// 1-based line and column
+    Line(usize, Option<usize>),
+    Node(usize),
+    Relation(String),
+    File(String),
+    /// An empty filter removes it
+    Filter(String),
+    /// Write the tuples in the tuples pane to a CSV file
+    Export(String),
+    Reload,
+}
+
+/// The names of the commands, in the order they are completed
+const COMMANDS: &[&str] = &["node", "rel", "file", "filter", "export", "reload"];
+
+impl Command {
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        let (name, arg) = match input.split_once(char::is_whitespace) {
+            Some((name, arg)) => (name, arg.trim()),
+            None => (input, ""),
+        };
+        let required = |what: &str| {
+            if arg.is_empty() {
+                Err(format!("`{}` needs {}", name, what))
+            } else {
+                Ok(arg.to_owned())
+            }
+        };
+
+        match name {
+            "" => Err(String::from("type a line number or a command")),
+            "node" => {
+                let id = required("a node id")?;
+                id.parse()
+                    .map(Command::Node)
+                    .map_err(|_| format!("`{}` is not a node id", id))
+            }
+            "rel" => Ok(Command::Relation(required("a relation")?)),
+            "file" => Ok(Command::File(required("a file")?)),
+            "filter" => Ok(Command::Filter(arg.to_owned())),
+            "export" => Ok(Command::Export(required("a file to write to")?)),
+            "reload" => Ok(Command::Reload),
+            _ if name.starts_with(|c: char| c.is_ascii_digit()) => {
+                let number = |n: &str| {
+                    n.parse::<usize>()
+                        .ok()
+                        .filter(|n| *n > 0)
+                        .ok_or_else(|| format!("`{}` is not a line or column number", n))
+                };
+                match input.split_once(':') {
+                    Some((line, column)) => Ok(Command::Line(number(line)?, Some(number(column)?))),
+                    None => Ok(Command::Line(number(input)?, None)),
+                }
+            }
+            _ => Err(format!("unknown command `{}`", name)),
+        }
+    }
+}
+
+/// Possible completions of `input`, as complete inputs. Without a space the command name is
+/// completed, otherwise its argument from `candidates(command)`. Arguments that start with what
+/// was typed come first, then the ones containing it.
+pub fn complete(input: &str, candidates: impl Fn(&str) -> Vec<String>) -> Vec<String> {
+    let Some((name, arg)) = input.split_once(' ') else {
+        return COMMANDS
+            .iter()
+            .filter(|c| c.starts_with(input))
+            .map(|c| format!("{} ", c))
+            .collect();
+    };
+
+    let arg = arg.trim_start();
+    let candidates = candidates(name);
+    let starting = candidates.iter().filter(|c| c.starts_with(arg));
+    let containing = candidates
+        .iter()
+        .filter(|c| !c.starts_with(arg) && c.contains(arg));
+
+    starting
+        .chain(containing)
+        .map(|c| format!("{} {}", name, c))
+        .collect()
+}
+
+/// Commands that were run, walked through with up and down like a shell history
+pub struct History {
+    entries: Vec<String>,
+    /// Position in `entries`, equal to its length when not walking through them
+    index: usize,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            entries: vec![],
+            index: 0,
+        }
+    }
+
+    /// Remember `command` and start walking from the newest entry again
+    pub fn push(&mut self, command: &str) {
+        let command = command.trim();
+        if !command.is_empty() && self.entries.last().map(String::as_str) != Some(command) {
+            self.entries.push(command.to_owned());
+        }
+        self.reset();
+    }
+
+    pub fn reset(&mut self) {
+        self.index = self.entries.len();
+    }
+
+    /// The entry before the current one, the oldest one stays
+    pub fn prev(&mut self) -> Option<&str> {
+        self.index = self.index.saturating_sub(1);
+        self.entries.get(self.index).map(String::as_str)
+    }
+
+    /// The entry after the current one, `None` past the newest one
+    pub fn next(&mut self) -> Option<&str> {
+        self.index = (self.index + 1).min(self.entries.len());
+        self.entries.get(self.index).map(String::as_str)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Command::parse("42"), Ok(Command::Line(42, None)));
+        assert_eq!(Command::parse(" 42:7 "), Ok(Command::Line(42, Some(7))));
+        assert_eq!(Command::parse("node 46"), Ok(Command::Node(46)));
+        assert_eq!(
+            Command::parse("rel  Call"),
+            Ok(Command::Relation(String::from("Call")))
+        );
+        assert_eq!(
+            Command::parse("filter node:14 file:*.c"),
+            Ok(Command::Filter(String::from("node:14 file:*.c")))
+        );
+        assert_eq!(Command::parse("filter"), Ok(Command::Filter(String::new())));
+        assert_eq!(Command::parse("reload"), Ok(Command::Reload));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Command::parse("").is_err());
+        assert!(Command::parse("0").is_err());
+        assert!(Command::parse("4x").is_err());
+        assert!(Command::parse("42:").is_err());
+        assert!(Command::parse("node").is_err());
+        assert!(Command::parse("node x").is_err());
+        assert!(Command::parse("export").is_err());
+        assert_eq!(
+            Command::parse("nodes 4"),
+            Err(String::from("unknown command `nodes`"))
+        );
+    }
+
+    #[test]
+    fn test_complete() {
+        let candidates = |command: &str| match command {
+            "rel" => vec![String::from("Call"), String::from("ExprPointsToArena")],
+            "file" => vec![String::from("src/test1.c"), String::from("test2.c")],
+            _ => vec![],
+        };
+        assert_eq!(complete("fi", candidates), vec!["file ", "filter "]);
+        assert_eq!(complete("rel C", candidates), vec!["rel Call"]);
+        assert_eq!(
+            complete("rel ", candidates),
+            vec!["rel Call", "rel ExprPointsToArena"]
+        );
+        assert_eq!(
+            complete("file test", candidates),
+            vec!["file test2.c", "file src/test1.c"]
+        );
+        assert!(complete("node 4", candidates).is_empty());
+    }
+
+    #[test]
+    fn test_history() {
+        let mut history = History::new();
+        assert_eq!(history.prev(), None);
+
+        history.push("42");
+        history.push("rel Call ");
+        history.push("rel Call");
+        history.push("");
+        assert_eq!(history.prev(), Some("rel Call"));
+        assert_eq!(history.prev(), Some("42"));
+        assert_eq!(history.prev(), Some("42"));
+        assert_eq!(history.next(), Some("rel Call"));
+        assert_eq!(history.next(), None);
+        assert_eq!(history.next(), None);
+        assert_eq!(history.prev(), Some("rel Call"));
+    }
+}
@@ -0,0 +1,243 @@
+use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
+use ratatui::{
+    prelude::{Constraint, Rect},
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, Cell, Row, Table, TableState},
+    Frame,
+};
+
+use crate::parsers::MetaInfo;
+
+/// A file matching the query, with the character positions that matched
+struct Match {
+    file: usize,
+    indices: Vec<usize>,
+}
+
+/// The `f` popup, narrows down the source files by fuzzy matching the typed query
+pub struct FilePicker {
+    pub query: String,
+    /// Every source file, sorted
+    files: Vec<String>,
+    /// The files matching `query`, best match first
+    matches: Vec<Match>,
+    pub state: TableState,
+    matcher: SkimMatcherV2,
+}
+
+impl FilePicker {
+    pub fn new(mut files: Vec<String>) -> Self {
+        files.sort();
+        let mut picker = Self {
+            query: String::new(),
+            files,
+            matches: vec![],
+            state: TableState::default(),
+            matcher: SkimMatcherV2::default().smart_case(),
+        };
+        picker.update();
+        picker
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Start over with an empty query
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.update();
+    }
+
+    pub fn push(&mut self, c: char) {
+        self.query.push(c);
+        self.update();
+    }
+
+    pub fn pop(&mut self) {
+        self.query.pop();
+        self.update();
+    }
+
+    /// Match the files against the query, an empty query matches every file
+    fn update(&mut self) {
+        let mut scored = self
+            .files
+            .iter()
+            .enumerate()
+            .filter_map(|(i, f)| {
+                if self.query.is_empty() {
+                    return Some((
+                        0,
+                        Match {
+                            file: i,
+                            indices: vec![],
+                        },
+                    ));
+                }
+                self.matcher
+                    .fuzzy_indices(f, &self.query)
+                    .map(|(score, indices)| (score, Match { file: i, indices }))
+            })
+            .collect::<Vec<_>>();
+        // Ties go to the shorter path, then alphabetical since `files` is sorted
+        scored.sort_by_key(|(score, m)| (-score, self.files[m.file].len(), m.file));
+
+        self.matches = scored.into_iter().map(|(_, m)| m).collect();
+        self.state.select(if self.matches.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    pub fn down(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some((i + 1).min(self.matches.len() - 1)));
+        }
+    }
+
+    pub fn up(&mut self) {
+        if let Some(i) = self.state.selected() {
+            self.state.select(Some(i.saturating_sub(1)));
+        }
+    }
+
+    /// The highlighted file
+    pub fn selected(&self) -> Option<&str> {
+        self.state
+            .selected()
+            .and_then(|i| self.matches.get(i))
+            .map(|m| self.files[m.file].as_str())
+    }
+
+    /// Show the matches with the number of tuples touching each file, overall and for `relation`
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        metainfo: &MetaInfo,
+        relation: Option<&str>,
+        block: Block,
+    ) {
+        let mut header = vec![Cell::from("all")];
+        if let Some(relation) = relation {
+            header.push(Cell::from(relation.to_owned()));
+        }
+        header.push(Cell::from(format!("> {}", self.query)));
+
+        let rows = self
+            .matches
+            .iter()
+            .map(|m| {
+                let file = &self.files[m.file];
+                let mut cells = vec![Cell::from(metainfo.tuple_count(file, None).to_string())];
+                if let Some(relation) = relation {
+                    let count = metainfo.tuple_count(file, Some(relation));
+                    cells.push(Cell::from(count.to_string()));
+                }
+                cells.push(Cell::from(highlight(file, &m.indices)));
+                Row::new(cells)
+            })
+            .collect::<Vec<_>>();
+
+        let count_width = relation.map_or(5, |r| r.chars().count().max(5)) as u16;
+        let mut widths = vec![Constraint::Length(5)];
+        if relation.is_some() {
+            widths.push(Constraint::Length(count_width));
+        }
+        widths.push(Constraint::Percentage(100));
+
+        let table = Table::new(rows)
+            .header(Row::new(header).style(Style::new().bold()))
+            .widths(&widths)
+            .highlight_symbol(">")
+            .block(block);
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+/// `file` with the characters at `indices` in bold
+fn highlight<'a>(file: &str, indices: &[usize]) -> Line<'a> {
+    let mut spans = vec![];
+    let mut acc = String::new();
+    let mut matched = false;
+    for (i, c) in file.chars().enumerate() {
+        if indices.contains(&i) != matched {
+            spans.push(styled(std::mem::take(&mut acc), matched));
+            matched = !matched;
+        }
+        acc.push(c);
+    }
+    spans.push(styled(acc, matched));
+
+    Line::from(spans)
+}
+
+fn styled<'a>(s: String, matched: bool) -> Span<'a> {
+    if matched {
+        Span::styled(s, Style::new().bold().light_yellow())
+    } else {
+        Span::raw(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn picker() -> FilePicker {
+        FilePicker::new(vec![
+            String::from("tests/clang/evaluation/src/arena/test1.c"),
+            String::from("tests/clang/evaluation/src/arena/test10.c"),
+            String::from("src/main.c"),
+        ])
+    }
+
+    #[test]
+    fn test_empty_query_lists_everything() {
+        let picker = picker();
+        assert_eq!(picker.matches.len(), 3);
+        assert_eq!(picker.selected(), Some("src/main.c"));
+    }
+
+    #[test]
+    fn test_ranking() {
+        let mut picker = picker();
+        for c in "arenatest1".chars() {
+            picker.push(c);
+        }
+        assert_eq!(picker.matches.len(), 2);
+        // Equal scores, the shorter path comes first
+        assert_eq!(
+            picker.selected(),
+            Some("tests/clang/evaluation/src/arena/test1.c")
+        );
+        picker.down();
+        picker.down();
+        assert_eq!(
+            picker.selected(),
+            Some("tests/clang/evaluation/src/arena/test10.c")
+        );
+
+        picker.push('z');
+        assert_eq!(picker.selected(), None);
+        picker.down();
+        picker.pop();
+        assert_eq!(picker.matches.len(), 2);
+        picker.clear();
+        assert_eq!(picker.matches.len(), 3);
+    }
+
+    #[test]
+    fn test_highlight() {
+        let line = highlight("abc", &[1, 2]);
+        let parts = line
+            .spans
+            .iter()
+            .map(|s| s.content.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(parts, vec!["a", "bc"]);
+    }
+}
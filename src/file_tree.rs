@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{ListItem, ListState},
+};
+
+use crate::parsers::MetaInfo;
+
+/// A directory or file in the tree
+struct Entry {
+    /// Directories that only contain a single directory are merged into one entry, `a/b/`
+    name: String,
+    /// Full path of a file, empty for directories
+    path: String,
+    depth: usize,
+    dir: bool,
+    expanded: bool,
+    /// Index of the first entry after the subtree of this one
+    end: usize,
+    /// Tuples touching the file, summed over the subtree for directories
+    count: usize,
+}
+
+#[derive(Default)]
+struct Dir<'a> {
+    dirs: BTreeMap<&'a str, Dir<'a>>,
+    files: BTreeMap<&'a str, &'a str>,
+}
+
+/// The source files as a collapsible directory tree
+pub struct FileTree {
+    /// Every entry in pre-order, directories before files
+    entries: Vec<Entry>,
+    /// Hide files without tuples for the selected relation, and directories without such files
+    pub hide_empty: bool,
+    /// The entries that are shown, as indices into `entries`
+    rows: Vec<usize>,
+    pub state: ListState,
+}
+
+impl FileTree {
+    pub fn new(files: &[String]) -> Self {
+        let mut root = Dir::default();
+        for file in files {
+            let mut dir = &mut root;
+            let mut parts = file.split('/').peekable();
+            while let Some(part) = parts.next() {
+                if parts.peek().is_none() {
+                    dir.files.insert(part, file);
+                } else {
+                    dir = dir.dirs.entry(part).or_default();
+                }
+            }
+        }
+
+        let mut tree = Self {
+            entries: vec![],
+            hide_empty: false,
+            rows: vec![],
+            state: ListState::default().with_selected(Some(0)),
+        };
+        tree.flatten(&root, 0);
+        tree.update_rows();
+        tree
+    }
+
+    fn flatten(&mut self, dir: &Dir, depth: usize) {
+        for (name, mut sub) in &dir.dirs {
+            let mut name = format!("{}/", name);
+            while sub.files.is_empty() && sub.dirs.len() == 1 {
+                let (next, next_sub) = sub.dirs.iter().next().unwrap();
+                name.push_str(next);
+                name.push('/');
+                sub = next_sub;
+            }
+
+            let i = self.entries.len();
+            self.entries.push(Entry {
+                name,
+                path: String::new(),
+                depth,
+                dir: true,
+                expanded: true,
+                end: 0,
+                count: 0,
+            });
+            self.flatten(sub, depth + 1);
+            self.entries[i].end = self.entries.len();
+        }
+        for (name, path) in &dir.files {
+            self.entries.push(Entry {
+                name: name.to_string(),
+                path: path.to_string(),
+                depth,
+                dir: false,
+                expanded: false,
+                end: self.entries.len() + 1,
+                count: 0,
+            });
+        }
+    }
+
+    /// Count the tuples of `relation` (or of every relation) touching each file and directory
+    pub fn set_relation(&mut self, metainfo: &MetaInfo, relation: Option<&str>) {
+        // Children come after their parent, so sum up from the back
+        for i in (0..self.entries.len()).rev() {
+            let entry = &self.entries[i];
+            let count = if entry.dir {
+                self.children(i).map(|c| self.entries[c].count).sum()
+            } else {
+                metainfo.tuple_count(&entry.path, relation)
+            };
+            self.entries[i].count = count;
+        }
+        self.update_rows();
+    }
+
+    /// The direct children of the entry at `i`
+    fn children(&self, i: usize) -> impl Iterator<Item = usize> + '_ {
+        let mut next = i + 1;
+        std::iter::from_fn(move || {
+            if next >= self.entries[i].end {
+                return None;
+            }
+            let child = next;
+            next = self.entries[child].end;
+            Some(child)
+        })
+    }
+
+    fn update_rows(&mut self) {
+        let selected = self.selected_entry();
+
+        self.rows.clear();
+        let mut i = 0;
+        while i < self.entries.len() {
+            let entry = &self.entries[i];
+            if self.hide_empty && entry.count == 0 {
+                i = entry.end;
+                continue;
+            }
+            self.rows.push(i);
+            i = if entry.dir && !entry.expanded {
+                entry.end
+            } else {
+                i + 1
+            };
+        }
+
+        // Keep the same entry selected if it is still shown
+        let row = selected
+            .and_then(|s| self.rows.iter().position(|r| *r == s))
+            .unwrap_or(0);
+        self.state.select(if self.rows.is_empty() {
+            None
+        } else {
+            Some(row)
+        });
+    }
+
+    fn selected_entry(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|r| self.rows.get(r))
+            .copied()
+    }
+
+    pub fn toggle_hide_empty(&mut self) {
+        self.hide_empty = !self.hide_empty;
+        self.update_rows();
+    }
+
+    pub fn down(&mut self) {
+        if let Some(r) = self.state.selected() {
+            self.state.select(Some((r + 1).min(self.rows.len() - 1)));
+        }
+    }
+
+    pub fn up(&mut self) {
+        if let Some(r) = self.state.selected() {
+            self.state.select(Some(r.saturating_sub(1)));
+        }
+    }
+
+    /// Expand the selected directory, or return the path of the selected file
+    pub fn open(&mut self) -> Option<String> {
+        let i = self.selected_entry()?;
+        if self.entries[i].dir {
+            self.entries[i].expanded = true;
+            self.update_rows();
+            None
+        } else {
+            Some(self.entries[i].path.clone())
+        }
+    }
+
+    /// Collapse the selected directory, or the directory containing the selected file
+    pub fn collapse(&mut self) {
+        let Some(i) = self.selected_entry() else {
+            return;
+        };
+        let dir = if self.entries[i].dir && self.entries[i].expanded {
+            Some(i)
+        } else {
+            (0..i)
+                .rev()
+                .find(|p| self.entries[*p].dir && self.entries[*p].end > i)
+        };
+        if let Some(dir) = dir {
+            self.entries[dir].expanded = false;
+            self.update_rows();
+            if let Some(r) = self.rows.iter().position(|r| *r == dir) {
+                self.state.select(Some(r));
+            }
+        }
+    }
+
+    /// Flip the selected directory between expanded and collapsed
+    pub fn toggle(&mut self) -> Option<String> {
+        match self.selected_entry() {
+            Some(i) if self.entries[i].dir && self.entries[i].expanded => {
+                self.collapse();
+                None
+            }
+            _ => self.open(),
+        }
+    }
+
+    /// Select the entry of `file`, expanding the directories it is in
+    pub fn reveal(&mut self, file: &str) {
+        let Some(i) = self.entries.iter().position(|e| e.path == file) else {
+            return;
+        };
+        for p in 0..i {
+            if self.entries[p].dir && self.entries[p].end > i {
+                self.entries[p].expanded = true;
+            }
+        }
+        self.update_rows();
+        if let Some(r) = self.rows.iter().position(|r| *r == i) {
+            self.state.select(Some(r));
+        }
+    }
+
+    /// `current` is the file open in the source view
+    pub fn widget<'a>(&self, current: &str) -> ratatui::widgets::List<'a> {
+        let items = self
+            .rows
+            .iter()
+            .map(|i| {
+                let entry = &self.entries[*i];
+                let marker = match (entry.dir, entry.expanded) {
+                    (true, true) => "▾ ",
+                    (true, false) => "▸ ",
+                    (false, _) => "  ",
+                };
+                let name = if entry.dir {
+                    Span::styled(entry.name.clone(), Style::new().bold())
+                } else if entry.path == current {
+                    Span::styled(entry.name.clone(), Style::new().light_green())
+                } else {
+                    Span::raw(entry.name.clone())
+                };
+                // Counts go first so long names do not push them out of the pane
+                let count = match entry.count {
+                    0 => Span::raw("     "),
+                    n => Span::styled(format!("{:>4} ", n), Style::new().dim()),
+                };
+                ListItem::new(Line::from(vec![
+                    count,
+                    Span::raw("  ".repeat(entry.depth)),
+                    Span::raw(marker),
+                    name,
+                ]))
+            })
+            .collect::<Vec<_>>();
+
+        ratatui::widgets::List::new(items).highlight_symbol(">")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(tree: &FileTree) -> Vec<&str> {
+        tree.rows
+            .iter()
+            .map(|i| tree.entries[*i].name.as_str())
+            .collect()
+    }
+
+    fn tree() -> FileTree {
+        FileTree::new(&[
+            String::from("tests/src/arena/test1.c"),
+            String::from("tests/src/arena/test2.c"),
+            String::from("tests/src/util.c"),
+            String::from("main.c"),
+        ])
+    }
+
+    #[test]
+    fn test_layout() {
+        let tree = tree();
+        assert_eq!(
+            names(&tree),
+            vec![
+                "tests/src/",
+                "arena/",
+                "test1.c",
+                "test2.c",
+                "util.c",
+                "main.c"
+            ]
+        );
+        assert_eq!(tree.entries[1].depth, 1);
+        assert_eq!(tree.children(0).collect::<Vec<_>>(), vec![1, 4]);
+    }
+
+    #[test]
+    fn test_collapse_and_open() {
+        let mut tree = tree();
+        tree.down();
+        tree.down();
+        tree.collapse();
+        assert_eq!(
+            names(&tree),
+            vec!["tests/src/", "arena/", "util.c", "main.c"]
+        );
+        assert_eq!(tree.state.selected(), Some(1));
+        assert_eq!(tree.open(), None);
+        tree.down();
+        assert_eq!(tree.open().as_deref(), Some("tests/src/arena/test1.c"));
+
+        tree.up();
+        tree.up();
+        tree.toggle();
+        assert_eq!(names(&tree), vec!["tests/src/", "main.c"]);
+        tree.reveal("tests/src/arena/test2.c");
+        assert_eq!(tree.state.selected(), Some(3));
+    }
+
+    #[test]
+    fn test_hide_empty() {
+        let mut tree = tree();
+        tree.entries[2].count = 3;
+        tree.entries[1].count = 3;
+        tree.entries[0].count = 3;
+        tree.toggle_hide_empty();
+        assert_eq!(names(&tree), vec!["tests/src/", "arena/", "test1.c"]);
+        tree.toggle_hide_empty();
+        assert_eq!(names(&tree).len(), 6);
+    }
+
+    #[test]
+    fn test_counts() {
+        let metainfo = MetaInfo::new("./example_data").unwrap();
+        let files = metainfo.source_files.keys().cloned().collect::<Vec<_>>();
+        let mut tree = FileTree::new(&files);
+        tree.set_relation(&metainfo, Some("Call"));
+        assert_eq!(tree.entries[0].count, 4);
+        tree.set_relation(&metainfo, Some("Nope"));
+        tree.toggle_hide_empty();
+        assert!(tree.rows.is_empty());
+        assert_eq!(tree.state.selected(), None);
+    }
+}
@@ -0,0 +1,209 @@
+use crate::parsers::{Field, Position, Span, Tuple};
+
+/// A filter over the tuples of a relation. It is written as whitespace separated terms that all
+/// have to match:
+///
+/// - `name=value` or `#2=value`: the column (by schema name or index) equals `value`
+/// - `node:14,46`: one of the node columns is one of the ids
+/// - `file:*test1.c`: a node is located in a file matching the glob (`*` and `?`)
+/// - `line:20-30` or `line:27`: a node is located on one of the lines
+/// - anything else: substring of one of the non-node columns
+#[derive(Debug, Clone, PartialEq)]
+pub struct Filter {
+    pub source: String,
+    terms: Vec<Term>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Equals(ColumnRef, String),
+    Nodes(Vec<usize>),
+    File(String),
+    Lines(usize, usize),
+    Contains(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum ColumnRef {
+    Index(usize),
+    Name(String),
+}
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, String> {
+        let terms = source
+            .split_whitespace()
+            .map(Term::parse)
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self {
+            source: source.split_whitespace().collect::<Vec<_>>().join(" "),
+            terms,
+        })
+    }
+
+    pub fn matches(&self, tuple: &Tuple) -> bool {
+        self.terms.iter().all(|t| t.matches(tuple))
+    }
+}
+
+impl Term {
+    fn parse(s: &str) -> Result<Self, String> {
+        if let Some(nodes) = s.strip_prefix("node:") {
+            let nodes = nodes
+                .split(',')
+                .map(|n| n.parse().map_err(|_| format!("`{}` is not a node id", n)))
+                .collect::<Result<Vec<_>, _>>()?;
+            return Ok(Term::Nodes(nodes));
+        }
+        if let Some(glob) = s.strip_prefix("file:") {
+            return Ok(Term::File(glob.to_owned()));
+        }
+        if let Some(lines) = s.strip_prefix("line:") {
+            let parse = |l: &str| {
+                l.parse::<usize>()
+                    .map_err(|_| format!("`{}` is not a line number", l))
+            };
+            return match lines.split_once('-') {
+                Some((from, to)) => Ok(Term::Lines(parse(from)?, parse(to)?)),
+                None => {
+                    let line = parse(lines)?;
+                    Ok(Term::Lines(line, line))
+                }
+            };
+        }
+        if let Some((column, value)) = s.split_once('=') {
+            if column.is_empty() {
+                return Err(format!("missing column in `{}`", s));
+            }
+            let column = match column.strip_prefix('#') {
+                Some(i) => ColumnRef::Index(
+                    i.parse()
+                        .map_err(|_| format!("`{}` is not a column index", i))?,
+                ),
+                None => ColumnRef::Name(column.to_owned()),
+            };
+            return Ok(Term::Equals(column, value.to_owned()));
+        }
+
+        Ok(Term::Contains(s.to_owned()))
+    }
+
+    fn matches(&self, tuple: &Tuple) -> bool {
+        match self {
+            Term::Equals(column, value) => {
+                let index = match column {
+                    ColumnRef::Index(i) => Some(*i),
+                    ColumnRef::Name(name) => tuple
+                        .columns
+                        .as_ref()
+                        .and_then(|c| c.iter().position(|c| c.name == *name)),
+                };
+                match index.and_then(|i| tuple.fields.get(i)) {
+                    Some(Field::Node(n)) => n.to_string() == *value,
+                    Some(Field::Value(v)) => v == value,
+                    None => false,
+                }
+            }
+            Term::Nodes(nodes) => tuple.nodes.iter().any(|n| nodes.contains(n)),
+            Term::File(glob) => tuple.located().any(|l| glob_match(glob, &l.source_file)),
+            Term::Lines(from, to) => {
+                let lines = Span::new(Position::new(*from, 0), Position::new(*to, usize::MAX));
+                tuple.located().any(|l| l.span().overlaps(&lines))
+            }
+            Term::Contains(s) => tuple.fields.iter().any(|f| match f {
+                Field::Value(v) => v.contains(s.as_str()),
+                Field::Node(_) => false,
+            }),
+        }
+    }
+}
+
+/// Match `text` against `glob` where `*` matches any sequence and `?` any single character
+fn glob_match(glob: &str, text: &str) -> bool {
+    let glob = glob.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut g, mut t) = (0, 0);
+    // Position of the last `*` and the text position it was tried at
+    let mut star: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if g < glob.len() && (glob[g] == '?' || glob[g] == text[t]) {
+            g += 1;
+            t += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            star = Some((g, t));
+            g += 1;
+        } else if let Some((sg, st)) = star {
+            g = sg + 1;
+            t = st + 1;
+            star = Some((sg, st + 1));
+        } else {
+            return false;
+        }
+    }
+
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::Loc;
+
+    fn tuple() -> Tuple {
+        let loc = Loc {
+            start_line: 27,
+            end_line: 28,
+            source_file: String::from("src/arena/test1.c"),
+            ..Default::default()
+        };
+        Tuple::new(
+            String::from("VarPointsToArena"),
+            vec![Field::Node(58), Field::Value(String::from("ptr"))],
+            vec![Some(loc)],
+        )
+    }
+
+    #[test]
+    fn test_terms() {
+        let t = tuple();
+        let matches = |s: &str| Filter::parse(s).unwrap().matches(&t);
+
+        assert!(matches(""));
+        assert!(matches("#0=58"));
+        assert!(matches("#1=ptr"));
+        assert!(!matches("#1=pt"));
+        assert!(!matches("name=ptr"));
+        assert!(matches("node:1,58"));
+        assert!(!matches("node:1"));
+        assert!(matches("file:*test1.c"));
+        assert!(matches("file:src/*/test?.c"));
+        assert!(!matches("file:*.h"));
+        assert!(matches("line:28"));
+        assert!(matches("line:1-27"));
+        assert!(!matches("line:29-40"));
+        assert!(matches("tr"));
+        assert!(!matches("58"));
+        assert!(matches("node:58 tr"));
+        assert!(!matches("node:58 nope"));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Filter::parse("node:x").is_err());
+        assert!(Filter::parse("line:1-x").is_err());
+        assert!(Filter::parse("#x=1").is_err());
+        assert!(Filter::parse("=1").is_err());
+        assert_eq!(Filter::parse("  a   b ").unwrap().source, "a b");
+    }
+
+    #[test]
+    fn test_glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("a*b*c", "aXbYc"));
+        assert!(glob_match("a*c", "abcbc"));
+        assert!(!glob_match("a*c", "abcb"));
+        assert!(!glob_match("?", ""));
+    }
+}
@@ -1,6 +1,11 @@
-use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 
-use crate::app_state::AppFocus;
+use crate::{
+    app_state::AppFocus,
+    inspector::Inspector,
+    keymap::{Action, Context},
+    tuple_table::SortBy,
+};
 
 pub fn handle_events(app_state: &mut crate::app_state::AppState) -> std::io::Result<()> {
     if let Event::Key(key) = event::read()? {
@@ -8,105 +13,306 @@ pub fn handle_events(app_state: &mut crate::app_state::AppState) -> std::io::Res
             return Ok(());
         }
 
-        if key.modifiers.contains(event::KeyModifiers::CONTROL) && key.code == KeyCode::Char('c')
-            || key.code == KeyCode::Char('q')
-        {
-            app_state.should_quit = true;
-            return Ok(());
+        handle_key(key, app_state);
+    }
+
+    Ok(())
+}
+
+pub fn handle_key(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
+    // Quitting with a control key (ctrl-c by default) still works while typing
+    if key.modifiers.contains(KeyModifiers::CONTROL)
+        && app_state.keymap.action(Context::Global, key.into()) == Some(Action::Quit)
+    {
+        app_state.should_quit = true;
+        return;
+    }
+
+    // Prompts take all keys so typing does not trigger other commands
+    match app_state.focus {
+        AppFocus::FilterPrompt => return handle_filter_prompt_inputs(key, app_state),
+        AppFocus::FilePicker => return handle_file_picker_inputs(key, app_state),
+        AppFocus::CommandLine => return handle_command_line_inputs(key, app_state),
+        AppFocus::SearchPrompt => {
+            handle_search_prompt_inputs(key, app_state);
+            app_state.mark_nodes_under_cursor();
+            return;
         }
+        _ => {}
+    }
 
-        match key.code {
-            KeyCode::Tab => {
-                app_state.focus = app_state.focus.next();
-            }
-            KeyCode::BackTab => {
-                app_state.focus = app_state.focus.prev();
-            }
-            KeyCode::Char('r') => {
-                app_state.focus = AppFocus::Relations;
+    let Some(action) = app_state
+        .keymap
+        .action(app_state.focus.context(), key.into())
+    else {
+        return;
+    };
+
+    match action {
+        Action::Quit => {
+            app_state.should_quit = true;
+            return;
+        }
+        Action::JumpBack => {
+            app_state.jump_back();
+            return;
+        }
+        Action::JumpForward => {
+            app_state.jump_forward();
+            return;
+        }
+        Action::FocusNext => {
+            app_state.focus = app_state.focus.next(app_state.show_tree);
+        }
+        Action::FocusPrev => {
+            app_state.focus = app_state.focus.prev(app_state.show_tree);
+        }
+        Action::FocusRelations => {
+            app_state.focus = AppFocus::Relations;
+        }
+        Action::FocusTuples => {
+            app_state.focus = AppFocus::Tuples;
+        }
+        Action::FocusSource => {
+            app_state.focus = AppFocus::Source;
+        }
+        Action::Command => return open_command_line(app_state, ""),
+        Action::GotoNode => return open_command_line(app_state, "node "),
+        Action::Inspect => {
+            if app_state.focus == AppFocus::Inspector {
+                app_state.focus = app_state.popup_from;
+            } else {
+                let inspector = Inspector::new(&app_state.metainfo, &app_state.current_nodes);
+                app_state.inspector = Some(inspector);
+                app_state.popup_from = app_state.focus;
+                app_state.focus = AppFocus::Inspector;
             }
-            KeyCode::Char('t') => {
-                app_state.focus = AppFocus::Tuples;
+            return;
+        }
+        Action::FilePicker => {
+            if !app_state.file_picker.is_empty() {
+                app_state.focus = AppFocus::FilePicker;
+                app_state.file_picker.clear();
             }
-            KeyCode::Char('s') => {
+            return;
+        }
+        Action::ToggleTree => {
+            if app_state.focus == AppFocus::Tree {
+                app_state.show_tree = false;
                 app_state.focus = AppFocus::Source;
+            } else if !app_state.file_picker.is_empty() {
+                app_state.show_tree = true;
+                app_state.focus = AppFocus::Tree;
+                let current = app_state.sv.name.clone();
+                app_state.file_tree.reveal(&current);
             }
-            KeyCode::Char('f') => {
-                if !app_state.files.items.is_empty() {
-                    app_state.focus = AppFocus::FilePicker;
-                }
+            return;
+        }
+        Action::Problems if !app_state.problems.items.is_empty() => {
+            app_state.focus = AppFocus::Problems;
+        }
+        Action::Help => {
+            if app_state.focus == AppFocus::Help {
+                app_state.focus = app_state.help_for;
+            } else {
+                app_state.help_for = app_state.focus;
+                app_state.help_scroll = 0;
+                app_state.focus = AppFocus::Help;
             }
-            KeyCode::Esc => {
-                if app_state.focus == AppFocus::FilePicker || app_state.focus == AppFocus::LinePicker {
-                    app_state.focus = AppFocus::Source;
-                } else {
-                    app_state.should_quit = true;
-                }
+            return;
+        }
+        Action::Close => {
+            if app_state.focus == AppFocus::Help {
+                app_state.focus = app_state.help_for;
+                return;
+            } else if app_state.focus == AppFocus::Inspector {
+                app_state.focus = app_state.popup_from;
+                return;
+            } else if app_state.focus == AppFocus::Problems {
+                app_state.focus = AppFocus::Source;
+            } else {
+                app_state.should_quit = true;
             }
+        }
+        _ => {}
+    };
+
+    if app_state.focus == AppFocus::Source {
+        handle_source_inputs(action, app_state);
+        app_state.mark_nodes_under_cursor();
+    } else if app_state.focus == AppFocus::Relations {
+        handle_relations_inputs(action, app_state);
+        app_state.update_view();
+        if let Some(s) = app_state.tuples.selected() {
+            s.set()
+        }
+    } else if app_state.focus == AppFocus::Tuples {
+        if action == Action::Filter {
+            app_state.focus = AppFocus::FilterPrompt;
+            app_state.input_buffer = app_state
+                .filter
+                .as_ref()
+                .map(|f| f.source.clone())
+                .unwrap_or_default();
+            app_state.input_error = None;
+            return;
+        }
+        // Prevent crashes by returning early
+        if app_state.tuples.items.is_empty() {
+            return;
+        }
+        handle_tuples_inputs(action, app_state);
+        app_state.update_view();
+    } else if app_state.focus == AppFocus::Tree {
+        handle_tree_inputs(action, app_state);
+    } else if app_state.focus == AppFocus::Problems {
+        handle_problems_inputs(action, app_state);
+    } else if app_state.focus == AppFocus::Inspector {
+        handle_inspector_inputs(action, app_state);
+    } else if app_state.focus == AppFocus::Help {
+        match action {
+            // Clamped when rendering, the height of the popup is not known here
+            Action::Down => app_state.help_scroll = app_state.help_scroll.saturating_add(1),
+            Action::Up => app_state.help_scroll = app_state.help_scroll.saturating_sub(1),
             _ => {}
-        };
+        }
+    }
+}
 
-        if app_state.focus == AppFocus::Source {
-            handle_source_inputs(key, app_state);
-            app_state.mark_nodes_under_cursor();
-        } else if app_state.focus == AppFocus::Relations {
-            handle_relations_inputs(key, app_state);
-            app_state.update_view();
-            if let Some(s) = app_state.tuples.selected() {
-                s.set()
+/// The character `key` types into a prompt, keys with ctrl or alt held do not type
+fn typed(key: event::KeyEvent) -> Option<char> {
+    match key.code {
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            Some(c)
+        }
+        _ => None,
+    }
+}
+
+/// The prompt binding of `key`, or `None` if it is not bound there
+fn prompt_action(key: event::KeyEvent, app_state: &crate::app_state::AppState) -> Option<Action> {
+    app_state.keymap.action(Context::Prompt, key.into())
+}
+
+fn handle_filter_prompt_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
+    match prompt_action(key, app_state) {
+        None => app_state.input_buffer.extend(typed(key)),
+        Some(Action::DeleteChar) => {
+            app_state.input_buffer.pop();
+        }
+        Some(Action::Close) => app_state.focus = AppFocus::Tuples,
+        Some(Action::Select) => {
+            let source = app_state.input_buffer.clone();
+            match app_state.set_filter(&source) {
+                Ok(()) => app_state.focus = AppFocus::Tuples,
+                Err(e) => app_state.input_error = Some(e),
             }
-        } else if app_state.focus == AppFocus::Tuples {
-            // Prevent crashes by returning early
-            if app_state.tuples.items.is_empty() {
-                return Ok(());
+        }
+        _ => {}
+    }
+}
+
+fn handle_search_prompt_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
+    match prompt_action(key, app_state) {
+        None => app_state.input_buffer.extend(typed(key)),
+        Some(Action::DeleteChar) => {
+            app_state.input_buffer.pop();
+        }
+        Some(Action::Close) => app_state.focus = AppFocus::Source,
+        Some(Action::Select) => {
+            let pattern = app_state.input_buffer.clone();
+            let backwards = app_state.search_backwards;
+            app_state.record_jump();
+            match app_state.sv.search(&pattern, backwards) {
+                Ok(()) => app_state.focus = AppFocus::Source,
+                Err(e) => app_state.input_error = Some(e),
             }
-            handle_tuples_inputs(key, app_state);
-            app_state.update_view();
-        } else if app_state.focus == AppFocus::FilePicker {
-            handle_file_picker_inputs(key, app_state);
-        } else if app_state.focus == AppFocus::LinePicker {
-            handle_line_picker_inputs(key, app_state);
         }
+        _ => {}
     }
+}
 
-    Ok(())
+fn open_command_line(app_state: &mut crate::app_state::AppState, input: &str) {
+    app_state.popup_from = app_state.focus;
+    app_state.focus = AppFocus::CommandLine;
+    app_state.input_buffer = input.to_owned();
+    app_state.input_error = None;
+    app_state.history.reset();
+    app_state.completions.clear();
+    app_state.completion = None;
 }
 
-fn handle_line_picker_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
-    match key.code {
-        KeyCode::Char('0') => app_state.input_buffer.push('0'),
-        KeyCode::Char('1') => app_state.input_buffer.push('1'),
-        KeyCode::Char('2') => app_state.input_buffer.push('2'),
-        KeyCode::Char('3') => app_state.input_buffer.push('3'),
-        KeyCode::Char('4') => app_state.input_buffer.push('4'),
-        KeyCode::Char('5') => app_state.input_buffer.push('5'),
-        KeyCode::Char('6') => app_state.input_buffer.push('6'),
-        KeyCode::Char('7') => app_state.input_buffer.push('7'),
-        KeyCode::Char('8') => app_state.input_buffer.push('8'),
-        KeyCode::Char('9') => app_state.input_buffer.push('9'),
-        KeyCode::Backspace => {
+fn handle_command_line_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
+    let action = prompt_action(key, app_state);
+    // Any other key starts completing from the new input
+    if !matches!(action, Some(Action::Complete | Action::CompletePrev)) {
+        app_state.completions.clear();
+        app_state.completion = None;
+    }
+
+    match action {
+        None => app_state.input_buffer.extend(typed(key)),
+        Some(Action::DeleteChar) => {
             app_state.input_buffer.pop();
         }
-        KeyCode::Enter => {
-            let target = app_state.input_buffer.parse::<u16>();
-            if let Ok(target) = target {
-                app_state.sv.move_to((0, target - 1));
+        Some(Action::Up) => {
+            if let Some(command) = app_state.history.prev() {
+                app_state.input_buffer = command.to_owned();
+            }
+        }
+        Some(Action::Down) => {
+            app_state.input_buffer = app_state.history.next().unwrap_or_default().to_owned();
+        }
+        Some(action @ (Action::Complete | Action::CompletePrev)) => {
+            if app_state.completion.is_none() {
+                app_state.completions = app_state.complete_command();
+            }
+            let n = app_state.completions.len();
+            if n == 0 {
+                app_state.input_error = Some(String::from("nothing to complete"));
+                return;
+            }
+            let i = match (app_state.completion, action) {
+                (None, Action::CompletePrev) => n - 1,
+                (None, _) => 0,
+                (Some(i), Action::CompletePrev) => (i + n - 1) % n,
+                (Some(i), _) => (i + 1) % n,
+            };
+            app_state.completion = Some(i);
+            app_state.input_buffer = app_state.completions[i].clone();
+        }
+        Some(Action::Close) => app_state.focus = app_state.popup_from,
+        Some(Action::Select) => {
+            let command = app_state.input_buffer.clone();
+            app_state.focus = app_state.popup_from;
+            match app_state.run_command(&command) {
+                Ok(()) => app_state.history.push(&command),
+                Err(e) => {
+                    app_state.focus = AppFocus::CommandLine;
+                    app_state.input_error = Some(e);
+                    return;
+                }
             }
-            app_state.focus = AppFocus::Source;
         }
         _ => {}
     };
+    app_state.input_error = None;
 }
 
-fn handle_relations_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
-    match key.code {
-        KeyCode::Char('j') | KeyCode::Down => {
+fn handle_relations_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    match action {
+        Action::Down => {
             app_state.relations.down();
         }
-        KeyCode::Char('k') | KeyCode::Up => {
+        Action::Up => {
             app_state.relations.up();
         }
-        KeyCode::Enter => {
+        Action::Select => {
+            app_state.record_jump();
             if let Some(s) = app_state.relations.selected() {
                 s.unmark();
             }
@@ -122,72 +328,161 @@ fn handle_relations_inputs(key: event::KeyEvent, app_state: &mut crate::app_stat
 }
 
 fn handle_file_picker_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
-    match key.code {
-        KeyCode::Char('j') | KeyCode::Down => app_state.files.down(),
-        KeyCode::Char('k') | KeyCode::Up => app_state.files.up(),
-        KeyCode::Enter => {
-            app_state.files.confirm();
-            let file = app_state.files.selected().unwrap().clone();
-            app_state.load_file(&file);
-            app_state.focus = AppFocus::Source;
+    match prompt_action(key, app_state) {
+        None => {
+            if let Some(c) = typed(key) {
+                app_state.file_picker.push(c);
+            }
+        }
+        Some(Action::Down) => app_state.file_picker.down(),
+        Some(Action::Up) => app_state.file_picker.up(),
+        Some(Action::DeleteChar) => app_state.file_picker.pop(),
+        Some(Action::Close) => app_state.focus = AppFocus::Source,
+        Some(Action::Select) => {
+            if let Some(file) = app_state.file_picker.selected().map(str::to_owned) {
+                app_state.record_jump();
+                app_state.load_file(&file);
+                app_state.focus = AppFocus::Source;
+            }
         }
         _ => {}
     }
 }
 
-fn handle_source_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
-    match key.code {
-        KeyCode::Char('j') | KeyCode::Down => {
+fn handle_tree_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    let file = match action {
+        Action::Down => {
+            app_state.file_tree.down();
+            None
+        }
+        Action::Up => {
+            app_state.file_tree.up();
+            None
+        }
+        Action::Expand => app_state.file_tree.open(),
+        Action::Collapse => {
+            app_state.file_tree.collapse();
+            None
+        }
+        Action::ToggleHideEmpty => {
+            app_state.file_tree.toggle_hide_empty();
+            None
+        }
+        Action::Select => app_state.file_tree.toggle(),
+        _ => None,
+    };
+    if let Some(file) = file {
+        app_state.record_jump();
+        app_state.load_file(&file);
+    }
+}
+
+fn handle_problems_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    match action {
+        Action::Down => app_state.problems.down(),
+        Action::Up => app_state.problems.up(),
+        _ => {}
+    }
+}
+
+fn handle_inspector_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    let Some(inspector) = &mut app_state.inspector else {
+        return;
+    };
+    match action {
+        Action::Down => inspector.down(),
+        Action::Up => inspector.up(),
+        Action::Select => {
+            if let Some((tuple, node)) = inspector.selected() {
+                let tuple = tuple.clone();
+                app_state.show_tuple(&tuple, node);
+                app_state.focus = AppFocus::Tuples;
+            }
+        }
+        _ => {}
+    }
+}
+
+fn handle_source_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    match action {
+        Action::Down => {
             app_state
                 .sv
                 .move_cursor(crate::source_view::Direction::Down);
         }
-        KeyCode::Char('k') | KeyCode::Up => {
+        Action::Up => {
             app_state.sv.move_cursor(crate::source_view::Direction::Up);
         }
-        KeyCode::Char('l') | KeyCode::Right => {
+        Action::Right => {
             app_state
                 .sv
                 .move_cursor(crate::source_view::Direction::Right);
         }
-        KeyCode::Char('h') | KeyCode::Left => {
+        Action::Left => {
             app_state
                 .sv
                 .move_cursor(crate::source_view::Direction::Left);
         }
-        KeyCode::Char('g') | KeyCode::Home => {
+        Action::GotoStart => {
+            app_state.record_jump();
             app_state.sv.move_to_start();
         }
-        KeyCode::Char('G') | KeyCode::End => {
+        Action::GotoEnd => {
+            app_state.record_jump();
             app_state.sv.move_to_end();
         }
-        KeyCode::Char(':') => {
-            app_state.focus = AppFocus::LinePicker;
+        Action::Search | Action::SearchBackward => {
+            app_state.focus = AppFocus::SearchPrompt;
+            app_state.search_backwards = action == Action::SearchBackward;
             app_state.input_buffer.clear();
+            app_state.input_error = None;
         }
+        Action::SearchNext => {
+            app_state.record_jump();
+            app_state.sv.search_next(false);
+        }
+        Action::SearchPrev => {
+            app_state.record_jump();
+            app_state.sv.search_next(true);
+        }
+        Action::OuterNode => app_state.move_node_focus(true),
+        Action::InnerNode => app_state.move_node_focus(false),
         _ => {}
     }
 }
 
-fn handle_tuples_inputs(key: event::KeyEvent, app_state: &mut crate::app_state::AppState) {
-    match key.code {
-        KeyCode::Char('j') | KeyCode::Down => {
+fn handle_tuples_inputs(action: Action, app_state: &mut crate::app_state::AppState) {
+    match action {
+        Action::Down => {
             app_state.tuples.down();
         }
-        KeyCode::Char('k') | KeyCode::Up => {
+        Action::Up => {
             app_state.tuples.up();
         }
-        KeyCode::Char('l') | KeyCode::Right => {
+        Action::NextNode => {
+            app_state.record_jump();
             if let Some(s) = app_state.tuples.selected() {
                 s.next()
             }
         }
-        KeyCode::Char('h') | KeyCode::Left => {
+        Action::PrevNode => {
+            app_state.record_jump();
             if let Some(s) = app_state.tuples.selected() {
                 s.prev()
             }
         }
-        KeyCode::Enter => {
+        Action::NextColumn => app_state.tuple_table.next_column(),
+        Action::PrevColumn => app_state.tuple_table.prev_column(),
+        Action::Widen => app_state.tuple_table.widen(),
+        Action::Narrow => app_state.tuple_table.narrow(),
+        Action::SortByValue => app_state
+            .tuple_table
+            .sort(&mut app_state.tuples, SortBy::Value),
+        Action::SortByLocation => app_state
+            .tuple_table
+            .sort(&mut app_state.tuples, SortBy::Location),
+        Action::Select => {
+            app_state.record_jump();
             if let Some(s) = app_state.tuples.selected() {
                 s.unset()
             }
@@ -0,0 +1,216 @@
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState},
+};
+
+use crate::parsers::{Loc, Located, MetaInfo, Tuple};
+
+/// Longest source text shown for a node
+const MAX_TEXT: usize = 60;
+
+enum Row {
+    Node {
+        /// `node 14`, or the relation of a row location
+        title: String,
+        loc: Option<Loc>,
+        text: String,
+    },
+    Relation(String),
+    Tuple {
+        tuple: Tuple,
+        /// The node of the heading above
+        node: Located,
+    },
+}
+
+/// The `i` popup, lists the nodes (and row locations) under the cursor and the tuples each of
+/// them is in
+pub struct Inspector {
+    rows: Vec<Row>,
+    pub state: ListState,
+}
+
+/// The source text of `loc` in `content`, on one line
+fn span_text(content: &str, loc: &Loc) -> String {
+    let span = loc.span();
+    let lines = content
+        .lines()
+        .zip(1..)
+        .filter(|(_, line_no)| span.lines().contains(line_no));
+
+    let mut parts = vec![];
+    for (line, line_no) in lines {
+        // Columns are 1-based chars and the end is inclusive
+        let start = if line_no == span.start.line {
+            span.start.col.saturating_sub(1)
+        } else {
+            0
+        };
+        let end = if line_no == span.end.line {
+            span.end.col
+        } else {
+            usize::MAX
+        };
+        let part = line.chars().take(end).skip(start).collect::<String>();
+        parts.push(part.trim().to_owned());
+    }
+
+    let text = parts.join(" ");
+    match text.char_indices().nth(MAX_TEXT) {
+        Some((i, _)) => format!("{}…", &text[..i]),
+        None => text,
+    }
+}
+
+impl Inspector {
+    pub fn new(metainfo: &MetaInfo, nodes: &[Located]) -> Self {
+        let mut rows = vec![];
+        for node in nodes {
+            let title = match node {
+                Located::Node(id) => format!("node {}", id),
+                Located::Tuple(..) => format!("{} row", metainfo.label(*node)),
+            };
+            let loc = metainfo.get_span(*node).cloned();
+            let text = loc
+                .as_ref()
+                .and_then(|l| {
+                    let source = metainfo.source_files.get(&l.source_file)?;
+                    Some(span_text(&source.content, l))
+                })
+                .unwrap_or_default();
+            rows.push(Row::Node { title, loc, text });
+
+            // Tuples come grouped by relation, so each relation is contiguous
+            for tuple in metainfo.get_analyses(&[*node]) {
+                let heading = match rows.last() {
+                    Some(Row::Tuple { tuple: t, .. }) => t.name != tuple.name,
+                    _ => true,
+                };
+                if heading {
+                    rows.push(Row::Relation(tuple.name.clone()));
+                }
+                rows.push(Row::Tuple { tuple, node: *node });
+            }
+        }
+
+        let mut inspector = Self {
+            rows,
+            state: ListState::default(),
+        };
+        inspector.down();
+        inspector
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Highlight the next tuple, headings are skipped
+    pub fn down(&mut self) {
+        let from = self.state.selected().map_or(0, |i| i + 1);
+        if let Some(i) =
+            (from..self.rows.len()).find(|i| matches!(self.rows[*i], Row::Tuple { .. }))
+        {
+            self.state.select(Some(i));
+        }
+    }
+
+    pub fn up(&mut self) {
+        let to = self.state.selected().unwrap_or(0);
+        if let Some(i) = (0..to)
+            .rev()
+            .find(|i| matches!(self.rows[*i], Row::Tuple { .. }))
+        {
+            self.state.select(Some(i));
+        }
+    }
+
+    /// The highlighted tuple and the node it was listed under
+    pub fn selected(&self) -> Option<(&Tuple, Located)> {
+        match self.rows.get(self.state.selected()?)? {
+            Row::Tuple { tuple, node } => Some((tuple, *node)),
+            _ => None,
+        }
+    }
+
+    pub fn widget<'a>(&self) -> List<'a> {
+        let items = self
+            .rows
+            .iter()
+            .map(|row| match row {
+                Row::Node { title, loc, text } => {
+                    let span = match loc {
+                        Some(l) => l.span().to_string(),
+                        None => String::from("no location"),
+                    };
+                    Line::from(vec![
+                        Span::styled(title.clone(), Style::new().bold()),
+                        Span::raw(format!("  {}  ", span)),
+                        Span::styled(text.clone(), Style::new().light_green()),
+                    ])
+                }
+                Row::Relation(name) => Line::styled(format!("  {}", name), Style::new().italic()),
+                Row::Tuple { tuple, .. } => Line::raw(format!("    {}", tuple.labels().join(", "))),
+            })
+            .map(ListItem::new)
+            .collect::<Vec<_>>();
+
+        List::new(items).highlight_symbol(">")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loc(start: (usize, usize), end: (usize, usize)) -> Loc {
+        Loc {
+            start_line: start.0,
+            start_col: start.1,
+            end_line: end.0,
+            end_col: end.1,
+            source_file: String::from("a.c"),
+        }
+    }
+
+    #[test]
+    fn test_span_text() {
+        let content = "int main() {\n  return f(1,\n           2);\n}";
+        assert_eq!(span_text(content, &loc((1, 5), (1, 8))), "main");
+        assert_eq!(span_text(content, &loc((2, 10), (3, 13))), "f(1, 2)");
+        assert_eq!(span_text(content, &loc((9, 1), (9, 2))), "");
+        assert_eq!(span_text("é = f(x);", &loc((1, 5), (1, 8))), "f(x)");
+        let long = "x".repeat(100);
+        assert_eq!(
+            span_text(&long, &loc((1, 1), (1, 100))).chars().count(),
+            MAX_TEXT + 1
+        );
+    }
+
+    #[test]
+    fn test_rows() {
+        let metainfo = MetaInfo::new("./example_data").unwrap();
+        let mut inspector = Inspector::new(&metainfo, &[Located::Node(14), Located::Node(46)]);
+
+        // The first tuple of node 14 is highlighted
+        let (tuple, node) = inspector.selected().unwrap();
+        assert_eq!((tuple.name.as_str(), node), ("Call", Located::Node(14)));
+        assert_eq!(tuple.nodes, vec![14, 46, 58]);
+
+        for _ in 0..20 {
+            inspector.down();
+        }
+        let (_, node) = inspector.selected().unwrap();
+        assert_eq!(node, Located::Node(46));
+        // Back on the first tuple, the node and relation rows above it are skipped
+        for _ in 0..20 {
+            inspector.up();
+        }
+        let (tuple, node) = inspector.selected().unwrap();
+        assert_eq!(node, Located::Node(14));
+        assert_eq!(tuple.nodes, vec![14, 46, 58]);
+
+        assert!(Inspector::new(&metainfo, &[]).is_empty());
+    }
+}
@@ -0,0 +1,121 @@
+use serde::{Deserialize, Serialize};
+
+use crate::parsers::Field;
+
+/// A tuple to select again, identified by its columns since filtering and sorting move it around
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TupleRef {
+    pub fields: Vec<Field>,
+    /// Index in `Tuple::locs` of the current location, see `Tuple::current_index`
+    pub loc: Option<usize>,
+}
+
+/// A place to jump back to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Jump {
+    pub file: String,
+    pub cursor: (u16, u16),
+    pub relation: Option<String>,
+    pub tuple: Option<TupleRef>,
+}
+
+/// The places visited before each jump, like the jump list of vim
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct JumpList {
+    jumps: Vec<Jump>,
+    /// Position in `jumps`, equal to its length when not going back through the list
+    index: usize,
+}
+
+/// Oldest jumps are dropped beyond this
+const MAX_JUMPS: usize = 100;
+
+impl JumpList {
+    pub fn new() -> Self {
+        Self {
+            jumps: vec![],
+            index: 0,
+        }
+    }
+
+    /// Remember `from` before jumping somewhere else, forgetting the jumps after the current one
+    pub fn record(&mut self, from: Jump) {
+        self.jumps.truncate(self.index);
+        if self.jumps.last() != Some(&from) {
+            self.jumps.push(from);
+        }
+        if self.jumps.len() > MAX_JUMPS {
+            self.jumps.remove(0);
+        }
+        self.index = self.jumps.len();
+    }
+
+    /// The jump before the current one, `current` is kept to come back to with `forward`
+    pub fn back(&mut self, current: Jump) -> Option<Jump> {
+        // Could be off in a hand-edited session file
+        self.index = self.index.min(self.jumps.len());
+        if self.index == 0 {
+            return None;
+        }
+        if self.index == self.jumps.len() {
+            self.jumps.push(current);
+        }
+        self.index -= 1;
+
+        Some(self.jumps[self.index].clone())
+    }
+
+    /// The jump after the current one, after going `back`
+    pub fn forward(&mut self) -> Option<Jump> {
+        if self.index + 1 >= self.jumps.len() {
+            return None;
+        }
+        self.index += 1;
+
+        Some(self.jumps[self.index].clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jump(line: u16) -> Jump {
+        Jump {
+            file: String::from("a.c"),
+            cursor: (0, line),
+            relation: None,
+            tuple: None,
+        }
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let mut list = JumpList::new();
+        assert_eq!(list.back(jump(0)), None);
+
+        list.record(jump(1));
+        list.record(jump(2));
+        list.record(jump(2));
+        assert_eq!(list.back(jump(3)), Some(jump(2)));
+        assert_eq!(list.back(jump(2)), Some(jump(1)));
+        assert_eq!(list.back(jump(1)), None);
+        assert_eq!(list.forward(), Some(jump(2)));
+        assert_eq!(list.forward(), Some(jump(3)));
+        assert_eq!(list.forward(), None);
+    }
+
+    #[test]
+    fn test_record_drops_forward_jumps() {
+        let mut list = JumpList::new();
+        list.record(jump(1));
+        list.record(jump(2));
+        list.back(jump(3));
+        list.back(jump(2));
+        // Jumping from 1 to 4 forgets about 2 and 3
+        list.record(jump(1));
+        assert_eq!(list.forward(), None);
+        assert_eq!(list.back(jump(4)), Some(jump(1)));
+        assert_eq!(list.forward(), Some(jump(4)));
+    }
+}
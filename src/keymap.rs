@@ -0,0 +1,634 @@
+use std::{collections::HashMap, fmt, path::Path, str::FromStr};
+
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+
+/// Where a binding applies, `Global` bindings apply everywhere unless a pane binds the same key.
+/// `Prompt` bindings apply while typing, where keys without a binding are typed rather than
+/// looked up globally.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Context {
+    Global,
+    Source,
+    Relations,
+    Tuples,
+    Tree,
+    Problems,
+    Help,
+    Inspector,
+    Prompt,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Quit,
+    /// Close a popup, or quit
+    Close,
+    FocusNext,
+    FocusPrev,
+    FocusRelations,
+    FocusTuples,
+    FocusSource,
+    FilePicker,
+    ToggleTree,
+    Problems,
+    Help,
+    JumpBack,
+    JumpForward,
+    Command,
+    GotoNode,
+    Inspect,
+    Down,
+    Up,
+    Left,
+    Right,
+    Select,
+    GotoStart,
+    GotoEnd,
+    Search,
+    SearchBackward,
+    SearchNext,
+    SearchPrev,
+    OuterNode,
+    InnerNode,
+    NextNode,
+    PrevNode,
+    NextColumn,
+    PrevColumn,
+    Widen,
+    Narrow,
+    SortByValue,
+    SortByLocation,
+    Filter,
+    Expand,
+    Collapse,
+    ToggleHideEmpty,
+    DeleteChar,
+    Complete,
+    CompletePrev,
+}
+
+impl Context {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Context::Global => "everywhere",
+            Context::Source => "source",
+            Context::Relations => "relations",
+            Context::Tuples => "tuples",
+            Context::Tree => "directories",
+            Context::Problems => "problems",
+            Context::Help => "help",
+            Context::Inspector => "inspector",
+            Context::Prompt => "prompts",
+        }
+    }
+}
+
+impl Action {
+    /// What the action does in `context`, for the help
+    pub fn description(&self, context: Context) -> &'static str {
+        match (self, context) {
+            (Action::Quit, _) => "quit",
+            (Action::Close, Context::Prompt) => "close the prompt",
+            (Action::Close, _) => "close the popup, or quit",
+            (Action::FocusNext, _) => "focus the next pane",
+            (Action::FocusPrev, _) => "focus the previous pane",
+            (Action::FocusRelations, _) => "focus the relations",
+            (Action::FocusTuples, _) => "focus the tuples",
+            (Action::FocusSource, _) => "focus the source",
+            (Action::FilePicker, _) => "open a file by name",
+            (Action::ToggleTree, _) => "show or hide the directory tree",
+            (Action::Problems, _) => "list the problems found while loading",
+            (Action::Help, _) => "show this help",
+            (Action::JumpBack, _) => "go back to where the cursor was before a jump",
+            (Action::JumpForward, _) => "go forward again after going back",
+            (Action::Command, _) => "run a command, or go to a line",
+            (Action::GotoNode, _) => "go to a node by id",
+            (Action::Inspect, _) => "list the nodes under the cursor and their tuples",
+            (Action::Down, Context::Source) => "move the cursor down",
+            (Action::Up, Context::Source) => "move the cursor up",
+            (Action::Down, Context::Help) => "scroll down",
+            (Action::Up, Context::Help) => "scroll up",
+            (Action::Down, Context::Prompt) => "select the next file, or a newer command",
+            (Action::Up, Context::Prompt) => "select the previous file, or an older command",
+            (Action::Down, _) => "highlight the next item",
+            (Action::Up, _) => "highlight the previous item",
+            (Action::Left, _) => "move the cursor left",
+            (Action::Right, _) => "move the cursor right",
+            (Action::Select, Context::Tree) => "open the file, or fold the directory",
+            (Action::Select, Context::Inspector) => "show the tuple in the tuples pane",
+            (Action::Select, Context::Prompt) => "accept the input",
+            (Action::Select, _) => "select the highlighted item",
+            (Action::GotoStart, _) => "go to the start of the file",
+            (Action::GotoEnd, _) => "go to the end of the file",
+            (Action::Search, _) => "search forwards with a regex",
+            (Action::SearchBackward, _) => "search backwards with a regex",
+            (Action::SearchNext, _) => "go to the next match",
+            (Action::SearchPrev, _) => "go to the previous match",
+            (Action::OuterNode, _) => "focus the node around the focused one",
+            (Action::InnerNode, _) => "focus the node inside the focused one",
+            (Action::NextNode, _) => "show the next node of the tuple",
+            (Action::PrevNode, _) => "show the previous node of the tuple",
+            (Action::NextColumn, _) => "select the next column",
+            (Action::PrevColumn, _) => "select the previous column",
+            (Action::Widen, _) => "widen the column",
+            (Action::Narrow, _) => "narrow the column",
+            (Action::SortByValue, _) => "sort by the column, again to reverse",
+            (Action::SortByLocation, _) => "sort by the location of the column, again to reverse",
+            (Action::Filter, _) => "filter the tuples",
+            (Action::Expand, _) => "expand the directory, or open the file",
+            (Action::Collapse, _) => "collapse the directory",
+            (Action::ToggleHideEmpty, _) => "hide files without tuples for the relation",
+            (Action::DeleteChar, _) => "delete the last character",
+            (Action::Complete, _) => "complete the command",
+            (Action::CompletePrev, _) => "go back to the previous completion",
+        }
+    }
+}
+
+/// The bindings used when there is no config file, and the actions that can be bound per context
+const DEFAULTS: &[(Context, Action, &[&str])] = &[
+    (Context::Global, Action::Quit, &["q", "ctrl-c"]),
+    (Context::Global, Action::Close, &["esc"]),
+    (Context::Global, Action::FocusNext, &["tab"]),
+    (Context::Global, Action::FocusPrev, &["backtab"]),
+    (Context::Global, Action::FocusRelations, &["r"]),
+    (Context::Global, Action::FocusTuples, &["t"]),
+    (Context::Global, Action::FocusSource, &["s"]),
+    (Context::Global, Action::FilePicker, &["f"]),
+    (Context::Global, Action::ToggleTree, &["d"]),
+    (Context::Global, Action::Problems, &["p"]),
+    // `?` opens the help everywhere, so searching backwards is on `\` rather than vim's `?`
+    (Context::Global, Action::Help, &["?", "f1"]),
+    (Context::Global, Action::JumpBack, &["ctrl-o"]),
+    // Most terminals send ctrl-i as tab, ctrl-f works everywhere
+    (Context::Global, Action::JumpForward, &["ctrl-i", "ctrl-f"]),
+    (Context::Global, Action::Command, &[":"]),
+    (Context::Global, Action::GotoNode, &["#"]),
+    (Context::Global, Action::Inspect, &["i"]),
+    (Context::Source, Action::Down, &["j", "down"]),
+    (Context::Source, Action::Up, &["k", "up"]),
+    (Context::Source, Action::Left, &["h", "left"]),
+    (Context::Source, Action::Right, &["l", "right"]),
+    (Context::Source, Action::GotoStart, &["g", "home"]),
+    (Context::Source, Action::GotoEnd, &["G", "end"]),
+    (Context::Source, Action::Search, &["/"]),
+    (Context::Source, Action::SearchBackward, &["\\"]),
+    (Context::Source, Action::SearchNext, &["n"]),
+    (Context::Source, Action::SearchPrev, &["N"]),
+    (Context::Source, Action::OuterNode, &["+"]),
+    (Context::Source, Action::InnerNode, &["-"]),
+    (Context::Relations, Action::Down, &["j", "down"]),
+    (Context::Relations, Action::Up, &["k", "up"]),
+    (Context::Relations, Action::Select, &["enter"]),
+    (Context::Tuples, Action::Down, &["j", "down"]),
+    (Context::Tuples, Action::Up, &["k", "up"]),
+    (Context::Tuples, Action::PrevNode, &["left", "h"]),
+    (Context::Tuples, Action::NextNode, &["right", "l"]),
+    (Context::Tuples, Action::PrevColumn, &["H"]),
+    (Context::Tuples, Action::NextColumn, &["L"]),
+    (Context::Tuples, Action::SortByValue, &["o"]),
+    (Context::Tuples, Action::SortByLocation, &["O"]),
+    (Context::Tuples, Action::Narrow, &["<"]),
+    (Context::Tuples, Action::Widen, &[">"]),
+    (Context::Tuples, Action::Filter, &["/"]),
+    (Context::Tuples, Action::Select, &["enter"]),
+    (Context::Tree, Action::Down, &["j", "down"]),
+    (Context::Tree, Action::Up, &["k", "up"]),
+    (Context::Tree, Action::Collapse, &["h", "left"]),
+    (Context::Tree, Action::Expand, &["l", "right"]),
+    (Context::Tree, Action::ToggleHideEmpty, &["e"]),
+    (Context::Tree, Action::Select, &["enter"]),
+    (Context::Problems, Action::Down, &["j", "down"]),
+    (Context::Problems, Action::Up, &["k", "up"]),
+    (Context::Help, Action::Down, &["j", "down"]),
+    (Context::Help, Action::Up, &["k", "up"]),
+    (Context::Inspector, Action::Down, &["j", "down"]),
+    (Context::Inspector, Action::Up, &["k", "up"]),
+    (Context::Inspector, Action::Select, &["enter"]),
+    (Context::Prompt, Action::Select, &["enter"]),
+    (Context::Prompt, Action::Close, &["esc"]),
+    (Context::Prompt, Action::DeleteChar, &["backspace"]),
+    (Context::Prompt, Action::Down, &["down", "ctrl-n"]),
+    (Context::Prompt, Action::Up, &["up", "ctrl-p"]),
+    (Context::Prompt, Action::Complete, &["tab"]),
+    (Context::Prompt, Action::CompletePrev, &["backtab"]),
+];
+
+/// A key with the modifiers that matter, written as `j`, `G`, `ctrl-o`, `alt-enter` or `pageup`
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Key {
+    pub code: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl From<KeyEvent> for Key {
+    fn from(event: KeyEvent) -> Self {
+        // Shift is already part of the character, or of backtab
+        Self {
+            code: event.code,
+            ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+            alt: event.modifiers.contains(KeyModifiers::ALT),
+        }
+    }
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("tab", KeyCode::Tab),
+    ("backtab", KeyCode::BackTab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+    ("space", KeyCode::Char(' ')),
+];
+
+impl FromStr for Key {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut key = Key {
+            code: KeyCode::Null,
+            ctrl: false,
+            alt: false,
+        };
+        let mut rest = s;
+        loop {
+            if let Some(r) = rest.strip_prefix("ctrl-").filter(|r| !r.is_empty()) {
+                key.ctrl = true;
+                rest = r;
+            } else if let Some(r) = rest.strip_prefix("alt-").filter(|r| !r.is_empty()) {
+                key.alt = true;
+                rest = r;
+            } else {
+                break;
+            }
+        }
+
+        let mut chars = rest.chars();
+        key.code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match NAMED_KEYS.iter().find(|(name, _)| *name == rest) {
+                Some((_, code)) => *code,
+                None => match rest.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=12) => KeyCode::F(n),
+                    _ => return Err(format!("unknown key `{}`", s)),
+                },
+            },
+        };
+
+        Ok(key)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "ctrl-")?;
+        }
+        if self.alt {
+            write!(f, "alt-")?;
+        }
+        match self.code {
+            KeyCode::Char(' ') => write!(f, "space"),
+            KeyCode::Char(c) => write!(f, "{}", c),
+            KeyCode::F(n) => write!(f, "f{}", n),
+            code => match NAMED_KEYS.iter().find(|(_, c)| *c == code) {
+                Some((name, _)) => write!(f, "{}", name),
+                None => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Keys {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// The config file, per context the keys of the actions to rebind. Rebinding an action replaces
+/// all of its default keys, `[]` unbinds it.
+type Config = HashMap<Context, HashMap<Action, Keys>>;
+
+/// Maps keys to actions
+pub struct Keymap {
+    /// In the order of `DEFAULTS`, the first key of an action is the one shown in titles
+    bindings: Vec<(Context, Action, Vec<Key>)>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = DEFAULTS
+            .iter()
+            .map(|(context, action, keys)| {
+                let keys = keys.iter().map(|k| k.parse().unwrap()).collect();
+                (*context, *action, keys)
+            })
+            .collect();
+
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// `keys.toml` or `keys.json` in the config directory
+    pub fn config_file() -> Option<std::path::PathBuf> {
+        let dir = dirs::config_dir()?.join("metavis");
+        ["keys.toml", "keys.json"]
+            .into_iter()
+            .map(|name| dir.join(name))
+            .find(|file| file.exists())
+    }
+
+    /// The default bindings with the ones in `file` on top
+    pub fn load(file: &Path) -> Result<Self, String> {
+        let content = std::fs::read_to_string(file).map_err(|e| e.to_string())?;
+        let config: Config = if file.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&content).map_err(|e| e.to_string())?
+        } else {
+            toml::from_str(&content).map_err(|e| e.to_string())?
+        };
+
+        let mut keymap = Self::default();
+        for (context, actions) in config {
+            for (action, keys) in actions {
+                let keys = match keys {
+                    Keys::One(key) => vec![key],
+                    Keys::Many(keys) => keys,
+                };
+                let keys = keys
+                    .iter()
+                    .map(|k| k.parse())
+                    .collect::<Result<Vec<Key>, _>>()?;
+                keymap.bind(context, action, keys)?;
+            }
+        }
+
+        Ok(keymap)
+    }
+
+    fn bind(&mut self, context: Context, action: Action, keys: Vec<Key>) -> Result<(), String> {
+        match self
+            .bindings
+            .iter_mut()
+            .find(|(c, a, _)| *c == context && *a == action)
+        {
+            Some(binding) => {
+                binding.2 = keys;
+                Ok(())
+            }
+            None => Err(format!("{:?} cannot be bound in {:?}", action, context)),
+        }
+    }
+
+    /// The action of `key` in `context`, falling back on the global bindings except in prompts
+    pub fn action(&self, context: Context, key: Key) -> Option<Action> {
+        let find = |context| {
+            self.bindings
+                .iter()
+                .find(|(c, _, keys)| *c == context && keys.contains(&key))
+                .map(|(_, a, _)| *a)
+        };
+        match context {
+            Context::Prompt => find(context),
+            _ => find(context).or_else(|| find(Context::Global)),
+        }
+    }
+
+    pub fn keys(&self, context: Context, action: Action) -> &[Key] {
+        self.bindings
+            .iter()
+            .find(|(c, a, _)| *c == context && *a == action)
+            .map_or(&[], |(_, _, keys)| keys.as_slice())
+    }
+
+    /// The bindings of `context`, in the order of `DEFAULTS`
+    pub fn bindings(&self, context: Context) -> impl Iterator<Item = (Action, &[Key])> {
+        self.bindings
+            .iter()
+            .filter(move |(c, _, _)| *c == context)
+            .map(|(_, a, keys)| (*a, keys.as_slice()))
+    }
+
+    /// The bindings of `context` and then the global ones, as (keys, description) per context
+    pub fn help(&self, context: Context) -> Vec<(Context, Vec<(String, &'static str)>)> {
+        let mut contexts = vec![context];
+        if context != Context::Global {
+            contexts.push(Context::Global);
+        }
+
+        contexts
+            .into_iter()
+            .map(|c| {
+                let lines = self
+                    .bindings(c)
+                    .map(|(action, keys)| {
+                        let keys = match keys {
+                            [] => String::from("(unbound)"),
+                            keys => keys
+                                .iter()
+                                .map(|k| k.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", "),
+                        };
+                        (keys, action.description(c))
+                    })
+                    .collect();
+                (c, lines)
+            })
+            .collect()
+    }
+
+    /// `name` with the key of `action` in brackets, as `[r]elations` or `relations [R]`
+    pub fn title(&self, name: &str, action: Action) -> String {
+        let Some(key) = self.keys(Context::Global, action).first() else {
+            return name.to_owned();
+        };
+        let key = key.to_string();
+        match name.find(key.as_str()) {
+            Some(i) if key.chars().count() == 1 => {
+                format!("{}[{}]{}", &name[..i], key, &name[i + key.len()..])
+            }
+            _ => format!("{} [{}]", name, key),
+        }
+    }
+
+    /// ` [key] label - [key/key] label ` for the bottom of a border, unbound actions are left out
+    pub fn hint(&self, context: Context, hints: &[(&[Action], &str)]) -> String {
+        let parts = hints
+            .iter()
+            .filter_map(|(actions, label)| {
+                let keys = actions
+                    .iter()
+                    .filter_map(|a| self.keys(context, *a).first())
+                    .map(|k| k.to_string())
+                    .collect::<Vec<_>>();
+                // `[//?]` would be hard to read
+                let separator = if keys.iter().any(|k| k == "/") {
+                    " "
+                } else {
+                    "/"
+                };
+                if keys.is_empty() {
+                    None
+                } else {
+                    Some(format!("[{}] {}", keys.join(separator), label))
+                }
+            })
+            .collect::<Vec<_>>();
+
+        format!(" {} ", parts.join(" - "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_root;
+
+    fn key(s: &str) -> Key {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        for s in [
+            "j",
+            "G",
+            "-",
+            "ctrl-o",
+            "ctrl-alt-x",
+            "enter",
+            "backtab",
+            "f5",
+            "space",
+        ] {
+            assert_eq!(key(s).to_string(), s);
+        }
+        assert!(key("ctrl--").ctrl);
+        assert!("ctrl-foo".parse::<Key>().is_err());
+        assert!("f13".parse::<Key>().is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let keymap = Keymap::default();
+        assert_eq!(keymap.action(Context::Source, key("j")), Some(Action::Down));
+        assert_eq!(
+            keymap.action(Context::Tuples, key("/")),
+            Some(Action::Filter)
+        );
+        assert_eq!(
+            keymap.action(Context::Source, key("/")),
+            Some(Action::Search)
+        );
+        assert_eq!(keymap.action(Context::Source, key("q")), Some(Action::Quit));
+        assert_eq!(keymap.action(Context::Source, key("x")), None);
+        assert_eq!(keymap.action(Context::Source, key("?")), Some(Action::Help));
+        let event = KeyEvent::new(KeyCode::Char('o'), KeyModifiers::CONTROL);
+        assert_eq!(
+            keymap.action(Context::Tuples, event.into()),
+            Some(Action::JumpBack)
+        );
+        // Keys without a prompt binding are typed
+        assert_eq!(
+            keymap.action(Context::Prompt, key("ctrl-n")),
+            Some(Action::Down)
+        );
+        assert_eq!(keymap.action(Context::Prompt, key("q")), None);
+    }
+
+    #[test]
+    fn test_titles() {
+        let keymap = Keymap::default();
+        assert_eq!(
+            keymap.title("relations", Action::FocusRelations),
+            "[r]elations"
+        );
+        assert_eq!(keymap.title("files", Action::FocusTuples), "files [t]");
+        assert_eq!(
+            keymap.hint(
+                Context::Source,
+                &[
+                    (&[Action::GotoStart], "goto start"),
+                    (&[Action::Search, Action::SearchBackward], "search"),
+                    (&[Action::Expand], "nothing"),
+                ]
+            ),
+            " [g] goto start - [/ \\] search "
+        );
+    }
+
+    #[test]
+    fn test_help() {
+        let mut keymap = Keymap::default();
+        keymap
+            .bind(Context::Source, Action::Search, vec![])
+            .unwrap();
+
+        let help = keymap.help(Context::Source);
+        assert_eq!(help.len(), 2);
+        assert_eq!(help[0].0, Context::Source);
+        assert_eq!(
+            help[0].1[0],
+            (String::from("j, down"), "move the cursor down")
+        );
+        assert!(help[0]
+            .1
+            .contains(&(String::from("(unbound)"), "search forwards with a regex")));
+        assert_eq!(help[1].0, Context::Global);
+        // Every bindable action is listed
+        let n = DEFAULTS
+            .iter()
+            .filter(|(c, _, _)| *c == Context::Source || *c == Context::Global)
+            .count();
+        assert_eq!(help.iter().map(|(_, lines)| lines.len()).sum::<usize>(), n);
+
+        assert_eq!(keymap.help(Context::Global).len(), 1);
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = write_root(&[(
+            "keys.toml",
+            "[global]\nfocus_source = \"S\"\n[source]\ndown = [\"s\", \"down\"]\nsearch = []\n",
+        )]);
+
+        let file = dir.join("keys.toml");
+        let keymap = Keymap::load(&file).unwrap();
+        assert_eq!(keymap.action(Context::Source, key("s")), Some(Action::Down));
+        assert_eq!(keymap.action(Context::Source, key("j")), None);
+        assert_eq!(keymap.action(Context::Source, key("/")), None);
+        assert_eq!(
+            keymap.action(Context::Tuples, key("S")),
+            Some(Action::FocusSource)
+        );
+        assert_eq!(keymap.title("source", Action::FocusSource), "source [S]");
+
+        let file = dir.join("keys.json");
+        std::fs::write(&file, r#"{"tuples": {"widen": "+"}}"#).unwrap();
+        let keymap = Keymap::load(&file).unwrap();
+        assert_eq!(
+            keymap.action(Context::Tuples, key("+")),
+            Some(Action::Widen)
+        );
+
+        std::fs::write(&file, r#"{"source": {"widen": "+"}}"#).unwrap();
+        assert!(Keymap::load(&file).is_err());
+        std::fs::write(&file, r#"{"source": {"fly": "+"}}"#).unwrap();
+        assert!(Keymap::load(&file).is_err());
+    }
+}
@@ -41,6 +41,10 @@ impl<T> List<T> {
         self.selected = self.state.selected();
     }
 
+    pub fn deselect(&mut self) {
+        self.selected = None;
+    }
+
     // Mark one or many items based on some filter
     pub fn mark(&mut self, func: impl Fn(&T) -> bool) {
         self.marked = self
@@ -51,6 +55,31 @@ impl<T> List<T> {
             .collect();
     }
 
+    /// Sort the items, the highlighted, selected and marked items stay the same
+    pub fn sort_by(&mut self, mut compare: impl FnMut(&T, &T) -> cmp::Ordering) {
+        let mut order = (0..self.items.len()).collect::<Vec<_>>();
+        order.sort_by(|a, b| compare(&self.items[*a], &self.items[*b]));
+
+        // old index -> new index
+        let mut moved = vec![0; order.len()];
+        for (new, old) in order.iter().enumerate() {
+            moved[*old] = new;
+        }
+
+        let mut items = std::mem::take(&mut self.items)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+        self.items = order.iter().map(|i| items[*i].take().unwrap()).collect();
+        self.selected = self.selected.map(|i| moved[i]);
+        self.marked = self.marked.iter().map(|i| moved[*i]).collect();
+        if let Some(i) = self.state.selected() {
+            if i < moved.len() {
+                self.state.select(Some(moved[i]));
+            }
+        }
+    }
+
     pub fn selected(&mut self) -> Option<&mut T> {
         match self.selected {
             Some(x) => self.items.get_mut(x),
@@ -1,8 +1,19 @@
 mod app_state;
+mod command;
+mod file_picker;
+mod file_tree;
+mod filter;
 mod input;
+mod inspector;
+mod jump_list;
+mod keymap;
 mod list;
 mod parsers;
+mod session;
 mod source_view;
+#[cfg(test)]
+mod test_util;
+mod tuple_table;
 mod ui;
 
 use ratatui::{prelude::CrosstermBackend, Terminal};
@@ -11,6 +22,8 @@ use std::io::stdout;
 pub fn initialize_panic_handler() {
     let original_hook = std::panic::take_hook();
     std::panic::set_hook(Box::new(move |panic_info| {
+        // Terminals without keyboard enhancement ignore this, so it is safe to always send
+        let _ = crossterm::execute!(stdout(), crossterm::event::PopKeyboardEnhancementFlags);
         crossterm::execute!(std::io::stderr(), crossterm::terminal::LeaveAlternateScreen).unwrap();
         crossterm::terminal::disable_raw_mode().unwrap();
         original_hook(panic_info);
@@ -18,20 +31,19 @@ pub fn initialize_panic_handler() {
 }
 
 fn make_app_state(source_dir: &str) -> anyhow::Result<app_state::AppState> {
-    let metainfo = parsers::MetaInfo::new(source_dir);
+    let metainfo = parsers::MetaInfo::new(source_dir)?;
 
-    let mut files = metainfo.source_files.keys().cloned().collect::<Vec<_>>();
-    files.sort();
-    let files = list::List::new(files);
+    let files = metainfo.source_files.keys().cloned().collect::<Vec<_>>();
+    let file_picker = file_picker::FilePicker::new(files);
 
-    let app_state = app_state::AppState::new(metainfo, files);
+    let app_state = app_state::AppState::new(metainfo, file_picker);
 
     Ok(app_state)
 }
 
 fn print_usage() {
     println!(
-        "Usage: {} <root>",
+        "Usage: {} [--fresh] [--tab-width <n>] <root>",
         std::env::current_exe()
             .unwrap()
             .file_name()
@@ -40,22 +52,40 @@ fn print_usage() {
             .unwrap()
     );
     println!("  <root>    The directory where debug.json resides (with source files in the same directory)");
+    println!("  --fresh   Do not restore the previous session for <root>");
+    println!(
+        "  --tab-width <n>  Columns between tab stops in the source, defaults to {}",
+        source_view::TAB_WIDTH
+    );
 }
 
 fn main() -> anyhow::Result<()> {
     coredump::register_panic_handler().unwrap();
     initialize_panic_handler();
 
-    let mut args = std::env::args();
-    args.next();
-    let root = match args.next() {
-        Some(arg) => {
-            if arg == "--help" || arg == "-h" {
+    let mut fresh = false;
+    let mut tab_width = source_view::TAB_WIDTH;
+    let mut root = None;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--help" | "-h" => {
                 print_usage();
                 return Ok(());
             }
-            arg
+            "--fresh" => fresh = true,
+            "--tab-width" => match args.next().and_then(|n| n.parse().ok()) {
+                Some(n) if n > 0 => tab_width = n,
+                _ => {
+                    eprintln!("error: --tab-width needs a positive number");
+                    std::process::exit(1);
+                }
+            },
+            _ => root = Some(arg),
         }
+    }
+    let root = match root {
+        Some(root) => root,
         None => {
             #[cfg(not(debug_assertions))]
             {
@@ -67,7 +97,38 @@ fn main() -> anyhow::Result<()> {
     };
 
     // Initalize app_state before chaning the terminal
-    let mut app_state = make_app_state(&root)?;
+    let mut app_state = match make_app_state(&root) {
+        Ok(app_state) => app_state,
+        Err(e) => {
+            eprintln!("error: could not load {}: {}", root, e);
+            std::process::exit(1);
+        }
+    };
+    app_state.sv.tab_width = tab_width;
+
+    if let Some(file) = keymap::Keymap::config_file() {
+        match keymap::Keymap::load(&file) {
+            Ok(keymap) => app_state.keymap = keymap,
+            Err(e) => app_state.problems.items.push(format!(
+                "could not load the key bindings from {}: {}",
+                file.display(),
+                e
+            )),
+        }
+    }
+
+    let session_file = session::default_path();
+    if let (Some(file), false) = (&session_file, fresh) {
+        match session::load(file, &root) {
+            Ok(Some(session)) => app_state.restore_session(session),
+            Ok(None) => {}
+            Err(e) => app_state.problems.items.push(format!(
+                "could not restore the session from {}: {}",
+                file.display(),
+                e
+            )),
+        }
+    }
 
     crossterm::execute!(std::io::stderr(), crossterm::terminal::EnterAlternateScreen)?;
     crossterm::execute!(
@@ -80,6 +141,16 @@ fn main() -> anyhow::Result<()> {
     terminal.clear()?;
     crossterm::terminal::enable_raw_mode()?;
     crossterm::execute!(std::io::stderr(), crossterm::terminal::EnterAlternateScreen)?;
+    // Tells ctrl-i apart from tab in terminals that support it
+    let enhanced_keys = crossterm::terminal::supports_keyboard_enhancement().unwrap_or(false);
+    if enhanced_keys {
+        crossterm::execute!(
+            stdout(),
+            crossterm::event::PushKeyboardEnhancementFlags(
+                crossterm::event::KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+            )
+        )?;
+    }
 
     // Main loop
     loop {
@@ -92,7 +163,20 @@ fn main() -> anyhow::Result<()> {
     }
 
     // shutdown down: reset terminal back to original state
+    if enhanced_keys {
+        crossterm::execute!(stdout(), crossterm::event::PopKeyboardEnhancementFlags)?;
+    }
     crossterm::execute!(std::io::stderr(), crossterm::terminal::LeaveAlternateScreen)?;
     crossterm::terminal::disable_raw_mode()?;
+
+    if let Some(file) = &session_file {
+        if let Err(e) = session::save(file, &root, app_state.take_session()) {
+            eprintln!(
+                "warning: could not save the session to {}: {}",
+                file.display(),
+                e
+            );
+        }
+    }
     Ok(())
 }
@@ -1,74 +1,225 @@
-use ratatui::text::Text;
+use ratatui::{
+    style::{Style, Stylize},
+    text::{Line, Span, Text},
+};
 
-use super::loc_file::Loc;
+use std::rc::Rc;
+
+use super::{csv_file::Field, debug_file::Column, loc_file::Loc};
 
 #[derive(Clone)]
 pub struct Tuple {
     pub name: String,
+    /// Every column in its original order
+    pub fields: Vec<Field>,
+    /// The node columns of `fields`
     pub nodes: Vec<usize>,
-    pub locs: Vec<Loc>,
+    /// The location of each node, `None` if it has no row in the loc file, followed by the
+    /// locations given by the row itself
+    pub locs: Vec<Option<Loc>>,
+    /// The columns of `fields` that each of `locs` comes from
+    loc_columns: Vec<Vec<usize>>,
+    /// Schema of the relation, if `debug.json` has one
+    pub columns: Option<Rc<Vec<Column>>>,
     current_loc: Option<usize>, // TODO: make private / find another way
 }
 
 impl Tuple {
-    pub fn new(name: String, nodes: Vec<usize>, locs: Vec<Loc>) -> Self {
+    /// `locs` holds one location per node column in `fields`
+    pub fn new(name: String, fields: Vec<Field>, locs: Vec<Option<Loc>>) -> Self {
+        let nodes = fields
+            .iter()
+            .filter_map(|f| match f {
+                Field::Node(n) => Some(*n),
+                Field::Value(_) => None,
+            })
+            .collect();
+        let loc_columns = fields
+            .iter()
+            .enumerate()
+            .filter(|(_, f)| matches!(f, Field::Node(_)))
+            .map(|(i, _)| vec![i])
+            .collect();
+
         Self {
             name,
+            fields,
             nodes,
             locs,
+            loc_columns,
+            columns: None,
             current_loc: None,
         }
     }
 
+    /// Add locations that are given by the columns of the row, after those of the nodes
+    pub fn with_locations(mut self, locations: Vec<(Vec<usize>, Loc)>) -> Self {
+        for (columns, loc) in locations {
+            self.loc_columns.push(columns);
+            self.locs.push(Some(loc));
+        }
+        self
+    }
+
+    pub fn with_columns(mut self, columns: Option<Rc<Vec<Column>>>) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Move to the next location, nodes without one are skipped
     pub fn next(&mut self) {
         let c = self.current_loc.unwrap_or(0);
-        self.current_loc = Some(std::cmp::min(c + 1, self.nodes.len() - 1));
+        if let Some(i) = (c + 1..self.locs.len()).find(|i| self.locs[*i].is_some()) {
+            self.current_loc = Some(i);
+        }
     }
 
+    /// Move to the previous location, nodes without one are skipped
     pub fn prev(&mut self) {
-        self.current_loc = match self.current_loc {
-            Some(0) => Some(0),
-            Some(x) => Some(x - 1),
-            _ => None,
-        };
+        if let Some(c) = self.current_loc {
+            if let Some(i) = (0..c).rev().find(|i| self.locs[*i].is_some()) {
+                self.current_loc = Some(i);
+            }
+        }
     }
 
     pub fn unset(&mut self) {
         self.current_loc = None;
     }
 
+    /// Select the first location
     pub fn set(&mut self) {
-        self.current_loc = Some(0);
+        self.current_loc = Some(self.locs.iter().position(|l| l.is_some()).unwrap_or(0));
+    }
+
+    /// Index of the current location in `locs`, if one is selected
+    pub fn current_index(&self) -> Option<usize> {
+        self.current_loc
+    }
+
+    /// Make the location at `index` of `locs` the current one, if the node has one
+    pub fn select_index(&mut self, index: usize) {
+        if self.locs.get(index).is_some_and(|l| l.is_some()) {
+            self.current_loc = Some(index);
+        }
     }
 
     pub fn current(&self) -> Option<&Loc> {
-        self.locs.get(self.current_loc.unwrap_or(0))
+        self.locs
+            .get(self.current_loc.unwrap_or(0))
+            .and_then(|l| l.as_ref())
     }
-}
 
-impl<'a> From<Tuple> for Text<'a> {
-    fn from(val: Tuple) -> Self {
-        let mut nodes_text = vec![];
-        let mut source_files = val
-            .locs
+    /// Every location of the tuple
+    pub fn located(&self) -> impl Iterator<Item = &Loc> {
+        self.locs.iter().flatten()
+    }
+
+    /// Nodes without a location
+    pub fn unlocated(&self) -> impl Iterator<Item = &usize> {
+        self.nodes
+            .iter()
+            .zip(self.locs.iter())
+            .filter(|(_, l)| l.is_none())
+            .map(|(n, _)| n)
+    }
+
+    /// Every column as text, node ids without a location get a `?` appended
+    pub fn labels(&self) -> Vec<String> {
+        let mut node = 0;
+        self.fields
             .iter()
+            .map(|f| match f {
+                Field::Node(n) => {
+                    node += 1;
+                    match self.locs[node - 1] {
+                        Some(_) => n.to_string(),
+                        None => format!("{}?", n),
+                    }
+                }
+                Field::Value(v) => v.clone(),
+            })
+            .collect()
+    }
+
+    /// The row as `(a,b,c)` where located columns are bold and the current location is marked
+    pub fn row<'a>(&self) -> Vec<Span<'a>> {
+        self.row_spans(false)
+    }
+
+    /// Same as `row` but every column is prefixed with its name from the schema, if any
+    pub fn named_row<'a>(&self) -> Vec<Span<'a>> {
+        self.row_spans(true)
+    }
+
+    fn row_spans<'a>(&self, named: bool) -> Vec<Span<'a>> {
+        let names = match (&self.columns, named) {
+            (Some(columns), true) => columns.iter().map(|c| Some(c.name.clone())).collect(),
+            _ => vec![None; self.fields.len()],
+        };
+        let mut spans = vec![Span::raw("(")];
+        for (i, (cell, name)) in self.cells().into_iter().zip(names).enumerate() {
+            if i > 0 {
+                spans.push(Span::raw(","));
+            }
+            if let Some(name) = name {
+                spans.push(Span::raw(format!("{}=", name)));
+            }
+            spans.push(cell);
+        }
+        spans.push(Span::raw(")"));
+
+        spans
+    }
+
+    /// One span per column, located columns are bold and the first column of the current
+    /// location is marked
+    pub fn cells<'a>(&self) -> Vec<Span<'a>> {
+        let current = self
+            .current_loc
+            .and_then(|c| self.loc_columns.get(c))
+            .and_then(|c| c.first());
+        self.labels()
+            .into_iter()
+            .enumerate()
+            .map(|(i, label)| {
+                let style = if self.loc_columns.iter().any(|c| c.contains(&i)) {
+                    Style::new().bold()
+                } else {
+                    Style::new().italic()
+                };
+                if current == Some(&i) {
+                    Span::styled(format!("*{}*", label), style)
+                } else {
+                    Span::styled(label, style)
+                }
+            })
+            .collect()
+    }
+
+    /// The location that column `column` is part of, `None` for other columns
+    pub fn loc_of_column(&self, column: usize) -> Option<&Loc> {
+        let i = self.loc_columns.iter().position(|c| c.contains(&column))?;
+        self.locs[i].as_ref()
+    }
+
+    /// The source files of the located nodes, without repeats
+    pub fn source_files(&self) -> Vec<String> {
+        let mut source_files = self
+            .located()
             .map(|l| l.source_file.clone())
             .collect::<Vec<_>>();
         source_files.dedup();
+        source_files
+    }
+}
 
-        for (i, n) in val.nodes.iter().enumerate() {
-            if val.current_loc.is_some() && i == val.current_loc.unwrap() {
-                nodes_text.push(format!("*{}*", n));
-            } else {
-                nodes_text.push(format!("{}", n));
-            }
-        }
+impl<'a> From<Tuple> for Text<'a> {
+    fn from(val: Tuple) -> Self {
+        let mut spans = val.row();
+        spans.push(Span::raw(format!(" @ {}", val.source_files().join(" & "))));
 
-        Text::from(format!(
-            "({}) @ {}",
-            nodes_text.join(","),
-            source_files.join(" & ")
-        ))
+        Text::from(Line::from(spans))
     }
 }
 
@@ -104,3 +255,109 @@ impl<'a> From<Relation> for Text<'a> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::debug_file::ColumnType;
+    use ratatui::style::Modifier;
+
+    fn loc(line: usize) -> Option<Loc> {
+        Some(Loc {
+            start_line: line,
+            end_line: line,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_cycle_skips_unlocated() {
+        let mut tuple = Tuple::new(
+            String::from("Call"),
+            vec![Field::Node(14), Field::Node(46), Field::Node(58)],
+            vec![loc(1), None, loc(3)],
+        );
+        tuple.set();
+        assert_eq!(tuple.current().unwrap().start_line, 1);
+        tuple.next();
+        assert_eq!(tuple.current().unwrap().start_line, 3);
+        tuple.next();
+        assert_eq!(tuple.current().unwrap().start_line, 3);
+        tuple.prev();
+        assert_eq!(tuple.current().unwrap().start_line, 1);
+        assert_eq!(tuple.labels(), vec!["14", "46?", "58"]);
+        assert_eq!(tuple.unlocated().collect::<Vec<_>>(), vec![&46]);
+    }
+
+    #[test]
+    fn test_labels_keep_column_order() {
+        let tuple = Tuple::new(
+            String::from("VarPointsToArena"),
+            vec![
+                Field::Value(String::from("p")),
+                Field::Node(58),
+                Field::Value(String::from("3")),
+            ],
+            vec![None],
+        );
+        assert_eq!(tuple.nodes, vec![58]);
+        assert_eq!(tuple.labels(), vec!["p", "58?", "3"]);
+    }
+
+    #[test]
+    fn test_named_row() {
+        let columns = vec![
+            Column {
+                name: String::from("var"),
+                kind: ColumnType::Symbol,
+            },
+            Column {
+                name: String::from("expr"),
+                kind: ColumnType::Node,
+            },
+        ];
+        let tuple = Tuple::new(
+            String::from("VarPointsToArena"),
+            vec![Field::Value(String::from("p")), Field::Node(58)],
+            vec![loc(1)],
+        )
+        .with_columns(Some(Rc::new(columns)));
+        let text = |spans: Vec<Span>| spans.into_iter().map(|s| s.content).collect::<String>();
+        assert_eq!(text(tuple.row()), "(p,58)");
+        assert_eq!(text(tuple.named_row()), "(var=p,expr=58)");
+    }
+
+    #[test]
+    fn test_inline_locations() {
+        let mut tuple = Tuple::new(
+            String::from("Warning"),
+            vec![
+                Field::Node(14),
+                Field::Value(String::from("a.c")),
+                Field::Value(String::from("3")),
+                Field::Value(String::from("1")),
+            ],
+            vec![None],
+        )
+        .with_locations(vec![(vec![1, 2, 3], loc(3).unwrap())]);
+        assert_eq!(tuple.located().count(), 1);
+        assert!(tuple.loc_of_column(0).is_none());
+        assert_eq!(tuple.loc_of_column(2).unwrap().start_line, 3);
+
+        tuple.set();
+        assert_eq!(tuple.current_index(), Some(1));
+        let text = |spans: Vec<Span>| spans.into_iter().map(|s| s.content).collect::<String>();
+        assert_eq!(text(tuple.row()), "(14?,*a.c*,3,1)");
+        assert!(tuple.cells()[3].style.add_modifier.contains(Modifier::BOLD));
+    }
+
+    #[test]
+    fn test_set_without_locations() {
+        let mut tuple = Tuple::new(String::from("Call"), vec![Field::Node(46)], vec![None]);
+        tuple.set();
+        assert!(tuple.current().is_none());
+        tuple.next();
+        tuple.prev();
+        assert!(tuple.current().is_none());
+    }
+}
@@ -1,28 +1,126 @@
+use csv::StringRecord;
+use serde::{Deserialize, Serialize};
+
+use super::{error::FieldError, loc_file::Loc};
+
+/// Reader for relation and loc files. There is no header, lines may have differing numbers of
+/// fields and fields may be quoted as described in RFC 4180.
+pub fn reader<R: std::io::Read>(rdr: R, delimiter: u8) -> csv::Reader<R> {
+    csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(rdr)
+}
+
+/// Split a single line into its fields
+pub fn parse_line(line: &str, delimiter: u8) -> StringRecord {
+    reader(line.as_bytes(), delimiter)
+        .records()
+        .next()
+        .and_then(|r| r.ok())
+        .unwrap_or_default()
+}
+
 #[derive(Debug, Clone)]
 pub struct RawAnalysis {
     pub name: String,
     pub nodes: Vec<usize>,
-    pub rest: String,
+    /// Every column in its original order
+    pub fields: Vec<Field>,
+    /// Locations read from the row itself, with the columns holding them
+    pub locations: Vec<(Vec<usize>, Loc)>,
+}
+
+/// A column of a relation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Field {
+    Node(usize),
+    Value(String),
 }
 
 impl RawAnalysis {
-    pub fn new(fields_with_nodes: &[usize], name: &str, s: &str) -> Self {
-        let splt = s.split(',');
+    pub fn new(
+        fields_with_nodes: &[usize],
+        name: &str,
+        record: &StringRecord,
+    ) -> Result<Self, FieldError> {
         let mut nodes = vec![];
-        let mut rest = vec![];
+        let mut fields = vec![];
 
-        for (i, s) in splt.enumerate() {
+        for (i, s) in record.iter().enumerate() {
             if fields_with_nodes.contains(&i) {
-                nodes.push(s.parse().unwrap());
+                let node = s
+                    .trim()
+                    .parse()
+                    .map_err(|_| FieldError::new(i, format!("`{}` is not a node id", s)))?;
+                nodes.push(node);
+                fields.push(Field::Node(node));
             } else {
-                rest.push(s);
+                fields.push(Field::Value(s.to_owned()));
             }
         }
 
-        Self {
+        if let Some(missing) = fields_with_nodes.iter().find(|i| **i >= fields.len()) {
+            return Err(FieldError::new(
+                *missing,
+                format!("is missing (line has {} columns)", fields.len()),
+            ));
+        }
+
+        Ok(Self {
             name: String::from(name),
             nodes,
-            rest: rest.join(","),
-        }
+            fields,
+            locations: vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_raw_analysis() {
+        let analysis = RawAnalysis::new(&[0, 2], "Call", &parse_line("14,foo,58", b',')).unwrap();
+        assert_eq!(analysis.nodes, vec![14, 58]);
+        assert_eq!(
+            analysis.fields,
+            vec![
+                Field::Node(14),
+                Field::Value(String::from("foo")),
+                Field::Node(58)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_analysis_bad_node() {
+        let err = RawAnalysis::new(&[0, 1, 2], "Call", &parse_line("14,46,abc", b',')).unwrap_err();
+        assert_eq!(err.column, 2);
+        assert_eq!(err.to_string(), "column 2 `abc` is not a node id");
+    }
+
+    #[test]
+    fn test_parse_raw_analysis_missing_column() {
+        let err = RawAnalysis::new(&[0, 1, 2], "Call", &parse_line("14,46", b',')).unwrap_err();
+        assert_eq!(err.column, 2);
+    }
+
+    #[test]
+    fn test_parse_quoted_fields() {
+        let record = parse_line(r#"14,"a, ""b""",58"#, b',');
+        let analysis = RawAnalysis::new(&[0, 2], "Call", &record).unwrap();
+        assert_eq!(analysis.nodes, vec![14, 58]);
+        assert_eq!(analysis.fields[1], Field::Value(String::from(r#"a, "b""#)));
+    }
+
+    #[test]
+    fn test_parse_tab_separated() {
+        let record = parse_line("14\ta,b\t58", b'\t');
+        let analysis = RawAnalysis::new(&[0, 2], "Call", &record).unwrap();
+        assert_eq!(analysis.nodes, vec![14, 58]);
+        assert_eq!(analysis.fields[1], Field::Value(String::from("a,b")));
     }
 }
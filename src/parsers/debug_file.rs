@@ -1,13 +1,294 @@
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
+
+use csv::StringRecord;
+
+use super::error::FieldError;
 
 #[derive(Deserialize, Debug, Default)]
 pub struct DebugJson {
     pub name: String,
     pub file: String,
-    #[serde(rename = "locs")]
+    /// May be left out if `columns` is given
+    #[serde(rename = "locs", default)]
     pub fields_with_nodes: Vec<usize>,
+    /// May be left out if the relation has no node columns
     #[serde(rename = "locFile")]
-    pub loc_file: String,
+    pub loc_file: Option<String>,
+    /// Field delimiter of `file`
+    #[serde(
+        default = "default_delimiter",
+        deserialize_with = "deserialize_delimiter"
+    )]
+    pub delimiter: u8,
+    /// Field delimiter of `loc_file`
+    #[serde(
+        rename = "locDelimiter",
+        default = "default_delimiter",
+        deserialize_with = "deserialize_delimiter"
+    )]
+    pub loc_delimiter: u8,
+    /// What the locations in `loc_file` are made of
+    #[serde(rename = "locFormat", default)]
+    pub loc_format: LocFormat,
+    /// How lines and columns are counted in `loc_file` and `locations`
+    #[serde(rename = "locConvention", default)]
+    pub loc_convention: LocConvention,
+    /// Optional schema of `file`, one entry per column
+    pub columns: Option<Vec<Column>>,
+    /// Locations given by columns of `file` rather than by a node id
+    #[serde(default)]
+    pub locations: Vec<LocationColumns>,
+}
+
+/// The (0-based) columns of `file` holding one location
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct LocationColumns {
+    pub file: usize,
+    pub line: usize,
+    pub col: usize,
+    /// Without an end the location is the single character at `line` and `col`
+    pub end_line: Option<usize>,
+    pub end_col: Option<usize>,
+}
+
+impl LocationColumns {
+    /// Every column of the location without repeats, the file first. `end_line` may be the
+    /// same column as `line` for locations on a single line.
+    pub fn columns(&self) -> Vec<usize> {
+        let all = [
+            Some(self.file),
+            Some(self.line),
+            Some(self.col),
+            self.end_line,
+            self.end_col,
+        ];
+        let mut columns = vec![];
+        for column in all.into_iter().flatten() {
+            if !columns.contains(&column) {
+                columns.push(column);
+            }
+        }
+        columns
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type", default)]
+    pub kind: ColumnType,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnType {
+    /// A node id that is looked up in the loc file
+    Node,
+    Number,
+    #[default]
+    Symbol,
+    /// Part of one of `locations`
+    Location,
+}
+
+/// The fields of a loc file after the node id and source file
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "camelCase")]
+pub enum LocFormat {
+    /// `start_line,start_col,end_line,end_col`
+    #[default]
+    LineColumn,
+    /// `start_offset,end_offset`, 0-based bytes into the source file with the end excluded
+    ByteOffset,
+}
+
+/// How a loc file counts lines and columns, metavis uses the default internally
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct LocConvention {
+    /// Number of the first line, 0 or 1
+    pub line_base: usize,
+    /// Number of the first column, 0 or 1
+    pub column_base: usize,
+    /// The end column is the last one of the span rather than the one after it
+    pub end_inclusive: bool,
+    pub column_unit: ColumnUnit,
+}
+
+impl Default for LocConvention {
+    fn default() -> Self {
+        Self {
+            line_base: 1,
+            column_base: 1,
+            end_inclusive: true,
+            column_unit: ColumnUnit::Char,
+        }
+    }
+}
+
+/// What a column counts
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColumnUnit {
+    /// UTF-8 bytes, as clang does
+    Byte,
+    Char,
+    /// UTF-16 code units, as Java does
+    Utf16,
+}
+
+impl DebugJson {
+    /// Check that `columns` agrees with `locs` and `locations`, filling in `locs` from `columns`
+    /// if it was left out, and that every column is used by at most one location
+    pub fn validate(&mut self) -> Result<(), String> {
+        let convention = &self.loc_convention;
+        if convention.line_base > 1 || convention.column_base > 1 {
+            return Err(String::from(
+                "the line and column base of locConvention are 0 or 1",
+            ));
+        }
+        // With byte offsets the convention is only used by `locations`
+        if self.loc_format == LocFormat::ByteOffset
+            && self.locations.is_empty()
+            && *convention != LocConvention::default()
+        {
+            return Err(String::from(
+                "locConvention does not apply to the byteOffset locFormat",
+            ));
+        }
+
+        if let Some(columns) = &self.columns {
+            let node_columns = columns
+                .iter()
+                .enumerate()
+                .filter(|(_, c)| c.kind == ColumnType::Node)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>();
+            if self.fields_with_nodes.is_empty() {
+                self.fields_with_nodes = node_columns;
+            } else if self.fields_with_nodes != node_columns {
+                return Err(format!(
+                    "locs {:?} do not match the node columns {:?}",
+                    self.fields_with_nodes, node_columns
+                ));
+            }
+        }
+        if self.loc_file.is_none() && !self.fields_with_nodes.is_empty() {
+            return Err(String::from("locFile is needed to locate the node columns"));
+        }
+
+        self.validate_locations()
+    }
+
+    /// Check that no column is part of two locations or of a location and `locs`, and that the
+    /// columns of `locations` are the `location` columns of the schema
+    fn validate_locations(&self) -> Result<(), String> {
+        let mut used = self.fields_with_nodes.clone();
+        let mut location_columns = vec![];
+        for location in &self.locations {
+            if location.end_line.is_some() != location.end_col.is_some() {
+                return Err(String::from(
+                    "a location needs both endLine and endCol or neither",
+                ));
+            }
+            for column in location.columns() {
+                if used.contains(&column) {
+                    return Err(format!("column {} is used by two locations", column));
+                }
+                used.push(column);
+                location_columns.push(column);
+            }
+        }
+
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+        if let Some(column) = location_columns.iter().find(|c| **c >= columns.len()) {
+            return Err(format!(
+                "column {} of a location is past the {} columns",
+                column,
+                columns.len()
+            ));
+        }
+        location_columns.sort();
+        let typed = columns
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.kind == ColumnType::Location)
+            .map(|(i, _)| i)
+            .collect::<Vec<_>>();
+        if location_columns != typed {
+            return Err(format!(
+                "the columns of locations {:?} do not match the location columns {:?}",
+                location_columns, typed
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check a record of `file` against `columns`, if there are any
+    pub fn check_record(&self, record: &StringRecord) -> Result<(), FieldError> {
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+        self.check_length(record)?;
+
+        for (i, (field, column)) in record.iter().zip(columns).enumerate() {
+            if column.kind == ColumnType::Number && field.trim().parse::<f64>().is_err() {
+                return Err(FieldError::new(
+                    i,
+                    format!("`{}` is not a number ({})", field, column.name),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that a record of `file` has as many fields as `columns`, if there are any
+    pub fn check_length(&self, record: &StringRecord) -> Result<(), FieldError> {
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+
+        if record.len() < columns.len() {
+            return Err(FieldError::new(
+                record.len(),
+                format!(
+                    "`{}` is missing, {} has {} columns",
+                    columns[record.len()].name,
+                    self.name,
+                    columns.len()
+                ),
+            ));
+        }
+        if record.len() > columns.len() {
+            return Err(FieldError::new(
+                columns.len(),
+                format!("is unexpected, {} has {} columns", self.name, columns.len()),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn default_delimiter() -> u8 {
+    b','
+}
+
+fn deserialize_delimiter<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u8, D::Error> {
+    let c = char::deserialize(deserializer)?;
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(serde::de::Error::custom(format!(
+            "delimiter `{}` is not a single ASCII character",
+            c
+        )))
+    }
 }
 
 #[cfg(test)]
@@ -31,6 +312,196 @@ mod tests {
         assert_eq!(debug_info[0].name, "Call");
         assert_eq!(debug_info[0].file, "Call.csv");
         assert_eq!(debug_info[0].fields_with_nodes, vec![0, 1, 2]);
-        assert_eq!(debug_info[0].loc_file, "DEBUG_Loc.csv");
+        assert_eq!(debug_info[0].loc_file.as_deref(), Some("DEBUG_Loc.csv"));
+        assert_eq!(debug_info[0].delimiter, b',');
+        assert_eq!(debug_info[0].loc_delimiter, b',');
+    }
+
+    #[test]
+    fn test_parse_delimiters() {
+        let json = r#"
+        [
+            {
+                "name": "Call",
+                "file": "Call.facts",
+                "locs": [0, 1, 2],
+                "locFile": "DEBUG_Loc.facts",
+                "delimiter": "\t",
+                "locDelimiter": ";"
+            }
+        ]
+        "#;
+        let debug_info = serde_json::from_str::<Vec<DebugJson>>(json).unwrap();
+        assert_eq!(debug_info[0].delimiter, b'\t');
+        assert_eq!(debug_info[0].loc_delimiter, b';');
+
+        let json = r#"[{"name": "A", "file": "A", "locs": [], "locFile": "L", "delimiter": "ab"}]"#;
+        assert!(serde_json::from_str::<Vec<DebugJson>>(json).is_err());
+    }
+
+    #[test]
+    fn test_parse_loc_convention() {
+        let json = r#"
+        [
+            {"name": "A", "file": "A.csv", "locs": [0], "locFile": "L.csv"},
+            {
+                "name": "B",
+                "file": "B.csv",
+                "locs": [0],
+                "locFile": "J.csv",
+                "locConvention": {"columnBase": 0, "endInclusive": false, "columnUnit": "utf16"}
+            }
+        ]
+        "#;
+        let mut debug_info = serde_json::from_str::<Vec<DebugJson>>(json).unwrap();
+        assert_eq!(debug_info[0].loc_convention, LocConvention::default());
+        assert_eq!(
+            debug_info[1].loc_convention,
+            LocConvention {
+                line_base: 1,
+                column_base: 0,
+                end_inclusive: false,
+                column_unit: ColumnUnit::Utf16,
+            }
+        );
+        assert!(debug_info[1].validate().is_ok());
+
+        debug_info[1].loc_convention.line_base = 2;
+        assert!(debug_info[1].validate().is_err());
+
+        assert_eq!(debug_info[0].loc_format, LocFormat::LineColumn);
+        debug_info[0].loc_format = LocFormat::ByteOffset;
+        assert!(debug_info[0].validate().is_ok());
+        debug_info[1].loc_convention.line_base = 1;
+        debug_info[1].loc_format = LocFormat::ByteOffset;
+        assert!(debug_info[1].validate().is_err());
+        // The convention still applies to inline locations
+        debug_info[1].locations = vec![LocationColumns {
+            file: 1,
+            line: 2,
+            col: 3,
+            end_line: None,
+            end_col: None,
+        }];
+        assert!(debug_info[1].validate().is_ok());
+        let json = r#"[{"name": "A", "file": "A", "locFile": "L", "locFormat": "byteOffset"}]"#;
+        let debug_info = serde_json::from_str::<Vec<DebugJson>>(json).unwrap();
+        assert_eq!(debug_info[0].loc_format, LocFormat::ByteOffset);
+
+        for convention in [r#"{"columnUnit": "nibble"}"#, r#"{"base": 0}"#] {
+            let json = format!(
+                r#"[{{"name": "A", "file": "A", "locFile": "L", "locConvention": {}}}]"#,
+                convention
+            );
+            assert!(serde_json::from_str::<Vec<DebugJson>>(&json).is_err());
+        }
+    }
+
+    #[test]
+    fn test_parse_locations() {
+        let json = r#"
+        [
+            {
+                "name": "Warning",
+                "file": "Warning.csv",
+                "locations": [
+                    {"file": 1, "line": 2, "col": 3},
+                    {"file": 4, "line": 5, "col": 6, "endLine": 7, "endCol": 8}
+                ]
+            }
+        ]
+        "#;
+        let mut debug_info = serde_json::from_str::<Vec<DebugJson>>(json).unwrap();
+        debug_info[0].validate().unwrap();
+        assert_eq!(debug_info[0].loc_file, None);
+        assert_eq!(debug_info[0].locations[0].columns(), vec![1, 2, 3]);
+        assert_eq!(debug_info[0].locations[1].columns(), vec![4, 5, 6, 7, 8]);
+        debug_info[0].locations[1].end_line = Some(5);
+        assert_eq!(debug_info[0].locations[1].columns(), vec![4, 5, 6, 8]);
+        debug_info[0].validate().unwrap();
+        debug_info[0].locations[1].end_line = Some(7);
+
+        // Nodes need a loc file
+        debug_info[0].fields_with_nodes = vec![0];
+        assert!(debug_info[0].validate().is_err());
+        debug_info[0].loc_file = Some(String::from("Loc.csv"));
+        debug_info[0].validate().unwrap();
+
+        debug_info[0].fields_with_nodes = vec![2];
+        assert!(debug_info[0].validate().is_err());
+        debug_info[0].fields_with_nodes = vec![];
+        debug_info[0].locations[1].file = 3;
+        assert!(debug_info[0].validate().is_err());
+        debug_info[0].locations[1].file = 4;
+        debug_info[0].locations[1].end_line = None;
+        assert!(debug_info[0].validate().is_err());
+        debug_info[0].locations[1].end_line = Some(7);
+
+        // A schema types the columns of locations as `location`
+        let column = |kind| Column {
+            name: String::from("c"),
+            kind,
+        };
+        let mut columns = vec![column(ColumnType::Symbol)];
+        columns.extend((1..=8).map(|_| column(ColumnType::Location)));
+        debug_info[0].columns = Some(columns);
+        debug_info[0].validate().unwrap();
+        debug_info[0].columns.as_mut().unwrap()[8].kind = ColumnType::Number;
+        assert!(debug_info[0].validate().is_err());
+        debug_info[0].columns.as_mut().unwrap().truncate(8);
+        assert_eq!(
+            debug_info[0].validate().unwrap_err(),
+            "column 8 of a location is past the 8 columns"
+        );
+    }
+
+    #[test]
+    fn test_parse_columns() {
+        let json = r#"
+        [
+            {
+                "name": "Call",
+                "file": "Call.csv",
+                "locFile": "DEBUG_Loc.csv",
+                "columns": [
+                    {"name": "site", "type": "node"},
+                    {"name": "depth", "type": "number"},
+                    {"name": "callee"}
+                ]
+            }
+        ]
+        "#;
+        let mut debug_info = serde_json::from_str::<Vec<DebugJson>>(json).unwrap();
+        debug_info[0].validate().unwrap();
+        assert_eq!(debug_info[0].fields_with_nodes, vec![0]);
+        let columns = debug_info[0].columns.as_ref().unwrap();
+        assert_eq!(columns[1].kind, ColumnType::Number);
+        assert_eq!(columns[2].kind, ColumnType::Symbol);
+
+        let d = &debug_info[0];
+        assert!(d
+            .check_record(&StringRecord::from(vec!["1", "2", "f"]))
+            .is_ok());
+        assert_eq!(
+            d.check_record(&StringRecord::from(vec!["1", "2"]))
+                .unwrap_err()
+                .column,
+            2
+        );
+        assert_eq!(
+            d.check_record(&StringRecord::from(vec!["1", "2", "f", "x"]))
+                .unwrap_err()
+                .column,
+            3
+        );
+        assert_eq!(
+            d.check_record(&StringRecord::from(vec!["1", "x", "f"]))
+                .unwrap_err()
+                .column,
+            1
+        );
+
+        debug_info[0].fields_with_nodes = vec![0, 2];
+        assert!(debug_info[0].validate().is_err());
     }
 }
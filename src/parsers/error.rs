@@ -0,0 +1,109 @@
+use std::fmt;
+
+/// A problem with a single field of a line in a CSV file
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldError {
+    /// Index of the offending column (0-based, same as `locs` in `debug.json`)
+    pub column: usize,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(column: usize, message: impl Into<String>) -> Self {
+        Self {
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "column {} {}", self.column, self.message)
+    }
+}
+
+/// Everything that can go wrong while loading an analysis root, either fatally or as a
+/// problem that is shown in the UI
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file could not be opened or read
+    Io {
+        file: String,
+        source: std::io::Error,
+    },
+    /// `debug.json` is not valid
+    Json {
+        file: String,
+        source: serde_json::Error,
+    },
+    /// An entry of `debug.json` is inconsistent
+    Schema {
+        file: String,
+        relation: String,
+        message: String,
+    },
+    /// A line in a relation or loc file could not be parsed
+    Row {
+        file: String,
+        /// 1-based line number
+        line: usize,
+        error: FieldError,
+    },
+    /// A relation mentions nodes that have no row in its loc file
+    Unlocated { file: String, nodes: Vec<usize> },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Io { file, source } => write!(f, "{}: {}", file, source),
+            LoadError::Json { file, source } => write!(f, "{}: {}", file, source),
+            LoadError::Schema {
+                file,
+                relation,
+                message,
+            } => write!(f, "{}: {}: {}", file, relation, message),
+            LoadError::Row { file, line, error } => write!(f, "{}:{}: {}", file, line, error),
+            LoadError::Unlocated { file, nodes } => write!(
+                f,
+                "{}: {} node(s) without a location: {}",
+                file,
+                nodes.len(),
+                nodes
+                    .iter()
+                    .map(|n| n.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadError::Io { source, .. } => Some(source),
+            LoadError::Json { source, .. } => Some(source),
+            LoadError::Schema { .. } | LoadError::Row { .. } | LoadError::Unlocated { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_row_error() {
+        let err = LoadError::Row {
+            file: String::from("Call.csv"),
+            line: 17,
+            error: FieldError::new(2, "`abc` is not a node id"),
+        };
+        assert_eq!(
+            err.to_string(),
+            "Call.csv:17: column 2 `abc` is not a node id"
+        );
+    }
+}
@@ -0,0 +1,173 @@
+/// A static interval tree over closed intervals `[start, end]`.
+///
+/// The intervals are kept sorted by start in a flat vector which is interpreted as an implicit
+/// binary search tree where every node stores the largest end in its subtree (the layout used by
+/// cgranges). Building is `O(n log n)` and a point query is `O(log n + k)` where `k` is the number
+/// of hits.
+#[derive(Debug, Clone)]
+pub struct IntervalTree<K, T> {
+    entries: Vec<Entry<K, T>>,
+    max_level: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry<K, T> {
+    start: K,
+    end: K,
+    /// Largest `end` in the subtree rooted at this entry
+    max: K,
+    value: T,
+}
+
+impl<K: Ord + Copy, T> IntervalTree<K, T> {
+    pub fn new(intervals: impl IntoIterator<Item = (K, K, T)>) -> Self {
+        let mut entries = intervals
+            .into_iter()
+            .map(|(start, end, value)| Entry {
+                start,
+                end,
+                max: end,
+                value,
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.start);
+        let max_level = Self::index(&mut entries);
+
+        Self { entries, max_level }
+    }
+
+    /// Compute `max` for every inner node and return the level of the root
+    fn index(entries: &mut [Entry<K, T>]) -> usize {
+        let n = entries.len();
+        if n == 0 {
+            return 0;
+        }
+
+        // Leaves are at even indices and `max` is already set to `end` for them
+        let mut last_i = 0;
+        let mut last = entries[0].end;
+        for i in (0..n).step_by(2) {
+            last_i = i;
+            last = entries[i].end;
+        }
+
+        let mut k = 1;
+        while 1 << k <= n {
+            let x = 1 << (k - 1);
+            let i0 = (x << 1) - 1;
+            let step = x << 2;
+            for i in (i0..n).step_by(step) {
+                let left = entries[i - x].max;
+                let right = if i + x < n { entries[i + x].max } else { last };
+                entries[i].max = entries[i].end.max(left).max(right);
+            }
+            last_i = if (last_i >> k) & 1 == 1 {
+                last_i - x
+            } else {
+                last_i + x
+            };
+            if last_i < n && entries[last_i].max > last {
+                last = entries[last_i].max;
+            }
+            k += 1;
+        }
+
+        k - 1
+    }
+
+    /// All values whose interval contains `point`, ordered by interval start
+    pub fn query(&self, point: K) -> Vec<&T> {
+        let n = self.entries.len();
+        if n == 0 {
+            return vec![];
+        }
+        let mut hits: Vec<&Entry<K, T>> = vec![];
+
+        // (node, level, whether the left subtree has been visited)
+        let mut stack = vec![((1 << self.max_level) - 1, self.max_level, false)];
+        while let Some((x, k, visited)) = stack.pop() {
+            if k <= 3 {
+                // Small subtree, scan it linearly
+                let i0 = x >> k << k;
+                let i1 = (i0 + (1 << (k + 1)) - 1).min(n);
+                for e in &self.entries[i0..i1] {
+                    if e.start > point {
+                        break;
+                    }
+                    if point <= e.end {
+                        hits.push(e);
+                    }
+                }
+            } else if !visited {
+                stack.push((x, k, true));
+                let y = x - (1 << (k - 1));
+                if y >= n || self.entries[y].max >= point {
+                    stack.push((y, k - 1, false));
+                }
+            } else if x < n && self.entries[x].start <= point {
+                let e = &self.entries[x];
+                if point <= e.end {
+                    hits.push(e);
+                }
+                stack.push((x + (1 << (k - 1)), k - 1, false));
+            }
+        }
+
+        hits.sort_by_key(|e| e.start);
+        hits.into_iter().map(|e| &e.value).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn brute_force(intervals: &[(u32, u32, usize)], point: u32) -> Vec<usize> {
+        let mut hits = intervals
+            .iter()
+            .filter(|(s, e, _)| *s <= point && point <= *e)
+            .map(|(_, _, v)| *v)
+            .collect::<Vec<_>>();
+        hits.sort();
+        hits
+    }
+
+    #[test]
+    fn test_empty() {
+        let tree = IntervalTree::<u32, ()>::new(vec![]);
+        assert!(tree.query(3).is_empty());
+    }
+
+    #[test]
+    fn test_nested() {
+        let tree = IntervalTree::new(vec![(0, 100, "fn"), (10, 20, "call"), (12, 14, "arg")]);
+        assert_eq!(tree.query(13), vec![&"fn", &"call", &"arg"]);
+        assert_eq!(tree.query(20), vec![&"fn", &"call"]);
+        assert_eq!(tree.query(21), vec![&"fn"]);
+        assert!(tree.query(101).is_empty());
+    }
+
+    #[test]
+    fn test_against_brute_force() {
+        // Deterministic pseudo random intervals, enough of them to get a deep tree
+        let mut seed = 42u32;
+        let mut next = || {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) % 1000
+        };
+        let intervals = (0..300)
+            .map(|i| {
+                let start = next();
+                let len = next() % 50;
+                (start, start + len, i)
+            })
+            .collect::<Vec<_>>();
+        let tree = IntervalTree::new(intervals.clone());
+
+        for point in 0..1100 {
+            let mut hits = tree.query(point).into_iter().copied().collect::<Vec<_>>();
+            hits.sort();
+            assert_eq!(hits, brute_force(&intervals, point), "point {}", point);
+        }
+    }
+}
@@ -1,17 +1,40 @@
+use super::loc_file::Position;
+
 #[derive(Default, Debug)]
 pub struct SourceFile {
     pub name: String,
     pub content: String,
+    /// Byte offset of the start of every line
+    line_starts: Vec<usize>,
 }
 
 impl SourceFile {
-    // pub fn new(name: &str, content: &str) -> Self {}
-
-    pub fn new_from_path(name: &str, path: &str) -> anyhow::Result<Self> {
-        let content = std::fs::read_to_string(path)?;
-        Ok(Self {
+    pub fn new(name: &str, content: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
             name: name.to_string(),
             content,
-        })
+            line_starts,
+        }
+    }
+
+    pub fn new_from_path(name: &str, path: &str) -> std::io::Result<Self> {
+        let content = std::fs::read_to_string(path)?;
+        Ok(Self::new(name, content))
+    }
+
+    /// The position of the char starting at byte `offset`, `None` if the offset is past the end
+    /// or inside a char
+    pub fn position(&self, offset: usize) -> Option<Position> {
+        if !self.content.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|s| *s <= offset);
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let col = self.content[start..offset].chars().count() + 1;
+
+        Some(Position::new(line, col))
     }
 }
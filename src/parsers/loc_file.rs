@@ -1,5 +1,74 @@
-use anyhow::{anyhow, Result};
-use std::str::FromStr;
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use csv::StringRecord;
+
+use super::{
+    csv_file,
+    debug_file::{ColumnUnit, LocConvention, LocationColumns},
+    error::FieldError,
+    lib::SourceFile,
+};
+
+/// A 1-based line and column, ordered by line and then column
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    pub line: usize,
+    pub col: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, col: usize) -> Self {
+        Self { line, col }
+    }
+}
+
+/// The positions from `start` to `end`, both included
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// The lines the span is on
+    pub fn lines(&self) -> RangeInclusive<usize> {
+        self.start.line..=self.end.line
+    }
+
+    pub fn contains_point(&self, point: Position) -> bool {
+        self.start <= point && point <= self.end
+    }
+
+    /// `other` is inside this span, equal spans contain each other
+    pub fn contains(&self, other: &Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// The spans have at least one position in common
+    pub fn overlaps(&self, other: &Span) -> bool {
+        self.start <= other.end && other.start <= self.end
+    }
+
+    /// The positions in both spans
+    pub fn intersection(&self, other: &Span) -> Option<Span> {
+        let span = Span::new(self.start.max(other.start), self.end.min(other.end));
+        (span.start <= span.end).then_some(span)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}-{}:{}",
+            self.start.line, self.start.col, self.end.line, self.end.col
+        )
+    }
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct Loc {
@@ -11,27 +80,95 @@ pub struct Loc {
 }
 
 impl Loc {
+    pub fn start(&self) -> Position {
+        Position::new(self.start_line, self.start_col)
+    }
+
+    pub fn end(&self) -> Position {
+        Position::new(self.end_line, self.end_col)
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.start(), self.end())
+    }
+
+    /// Convert from `convention` to 1-based lines and 1-based, inclusive columns counted in chars.
+    /// `lines` are the lines of the source file, only needed when columns are not chars.
+    pub fn normalize(&mut self, convention: &LocConvention, lines: &[&str]) {
+        if *convention == LocConvention::default() {
+            return;
+        }
+
+        self.start_line = (self.start_line + 1).saturating_sub(convention.line_base);
+        self.end_line = (self.end_line + 1).saturating_sub(convention.line_base);
+
+        let line = |n: usize| n.checked_sub(1).and_then(|i| lines.get(i)).unwrap_or(&"");
+        let unit = convention.column_unit;
+        // 0-based offsets in `unit`
+        let start = self.start_col.saturating_sub(convention.column_base);
+        let end = self.end_col.saturating_sub(convention.column_base);
+
+        self.start_col = char_index(line(self.start_line), start, unit) + 1;
+        // An exclusive end is one past the last char, which is the last char counting from 1
+        self.end_col = char_index(line(self.end_line), end, unit);
+        if convention.end_inclusive {
+            self.end_col += 1;
+        }
+    }
+
+    /// `other` is inside this loc, in the same file
     pub fn contains(&self, other: &Loc) -> bool {
-        self.start_line <= other.start_line
-            && self.end_line >= other.end_line
-            && self.start_col <= other.start_col
-            && self.end_col >= other.end_col
+        self.source_file == other.source_file && self.span().contains(&other.span())
     }
 
+    /// This loc is inside `other`, in the same file
     pub fn contained_by(&self, other: &Loc) -> bool {
-        !self.contains(other)
+        other.contains(self)
     }
 }
 
-impl FromStr for Loc {
-    type Err = anyhow::Error;
+/// The 0-based char of `line` that the unit at 0-based `offset` is part of
+fn char_index(line: &str, offset: usize, unit: ColumnUnit) -> usize {
+    let mut units = 0;
+    for (i, c) in line.chars().enumerate() {
+        units += match unit {
+            ColumnUnit::Byte => c.len_utf8(),
+            ColumnUnit::Char => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        };
+        if offset < units {
+            return i;
+        }
+    }
+    // Past the end of the line every unit counts as a char
+    line.chars().count() + (offset - units)
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut splt = s.split(',');
-        let start_line = splt.next().unwrap().parse()?;
-        let start_col = splt.next().unwrap().parse()?;
-        let end_line = splt.next().unwrap().parse()?;
-        let end_col = splt.next().unwrap().parse()?;
+/// Parse the next field of `splt` as a number, `column` is only used for error reporting
+fn parse_number<'a>(
+    splt: &mut impl Iterator<Item = &'a str>,
+    column: usize,
+    what: &str,
+) -> Result<usize, FieldError> {
+    let field = splt
+        .next()
+        .ok_or_else(|| FieldError::new(column, format!("is missing, expected {}", what)))?;
+    field
+        .trim()
+        .parse()
+        .map_err(|_| FieldError::new(column, format!("`{}` is not {}", field, what)))
+}
+
+impl Loc {
+    /// Parse the four numbers of a loc from `fields`, the first of which is column `first`
+    fn from_fields<'a>(
+        fields: &mut impl Iterator<Item = &'a str>,
+        first: usize,
+    ) -> Result<Self, FieldError> {
+        let start_line = parse_number(fields, first, "a start line")?;
+        let start_col = parse_number(fields, first + 1, "a start column")?;
+        let end_line = parse_number(fields, first + 2, "an end line")?;
+        let end_col = parse_number(fields, first + 3, "an end column")?;
 
         Ok(Loc {
             start_line,
@@ -41,6 +178,44 @@ impl FromStr for Loc {
             source_file: String::from(""),
         })
     }
+
+    /// Read the location in the `columns` of `record`, a row of a relation
+    pub fn from_columns(
+        record: &StringRecord,
+        columns: &LocationColumns,
+    ) -> Result<Self, FieldError> {
+        let number = |column: usize, what: &str| {
+            parse_number(&mut record.get(column).into_iter(), column, what)
+        };
+        let source_file = record
+            .get(columns.file)
+            .ok_or_else(|| FieldError::new(columns.file, "is missing, expected a source file"))?
+            .to_owned();
+        let start_line = number(columns.line, "a line")?;
+        let start_col = number(columns.col, "a column")?;
+        let (end_line, end_col) = match (columns.end_line, columns.end_col) {
+            (Some(line), Some(col)) => {
+                (number(line, "an end line")?, number(col, "an end column")?)
+            }
+            _ => (start_line, start_col),
+        };
+
+        Ok(Loc {
+            start_line,
+            start_col,
+            end_line,
+            end_col,
+            source_file,
+        })
+    }
+}
+
+impl FromStr for Loc {
+    type Err = FieldError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Loc::from_fields(&mut csv_file::parse_line(s, b',').iter(), 0)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -51,13 +226,23 @@ pub struct DebugLoc {
 }
 
 impl FromStr for DebugLoc {
-    type Err = anyhow::Error;
+    type Err = FieldError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut splt = s.split(',');
-        let node_id = splt.next().ok_or(anyhow!("missing node id"))?.parse()?;
-        let source_file = splt.next().ok_or(anyhow!("missing name"))?.to_owned();
-        let mut loc = Loc::from_str(&splt.collect::<Vec<&str>>().join(",")).unwrap();
+        DebugLoc::from_record(&csv_file::parse_line(s, b','))
+    }
+}
+
+impl DebugLoc {
+    /// Parse a `node_id,source_file,start_line,start_col,end_line,end_col` record
+    pub fn from_record(record: &StringRecord) -> Result<Self, FieldError> {
+        let mut fields = record.iter();
+        let node_id = parse_number(&mut fields, 0, "a node id")?;
+        let source_file = fields
+            .next()
+            .ok_or_else(|| FieldError::new(1, "is missing, expected a source file"))?
+            .to_owned();
+        let mut loc = Loc::from_fields(&mut fields, 2)?;
         loc.source_file = source_file.clone();
 
         Ok(DebugLoc {
@@ -66,6 +251,61 @@ impl FromStr for DebugLoc {
             loc,
         })
     }
+
+    /// Parse a `node_id,source_file,start_offset,end_offset` record, `source` is the content of
+    /// the source file if it could be read. An empty range is located at its start.
+    pub fn from_offset_record(
+        record: &StringRecord,
+        source: Option<&SourceFile>,
+    ) -> Result<Self, FieldError> {
+        let mut fields = record.iter();
+        let node_id = parse_number(&mut fields, 0, "a node id")?;
+        let source_file = fields
+            .next()
+            .ok_or_else(|| FieldError::new(1, "is missing, expected a source file"))?
+            .to_owned();
+        let start = parse_number(&mut fields, 2, "a start offset")?;
+        let end = parse_number(&mut fields, 3, "an end offset")?;
+
+        let source = source.ok_or_else(|| {
+            FieldError::new(1, format!("`{}` is needed to read offsets", source_file))
+        })?;
+        let position = |offset: usize, column: usize| {
+            source.position(offset).ok_or_else(|| {
+                let problem = if offset > source.content.len() {
+                    "is past the end of"
+                } else {
+                    "is inside a char of"
+                };
+                FieldError::new(column, format!("`{}` {} {}", offset, problem, source_file))
+            })
+        };
+        let start_position = position(start, 2)?;
+        if end < start {
+            return Err(FieldError::new(
+                3,
+                format!("`{}` is before the start offset", end),
+            ));
+        }
+        // The end is excluded, so the span ends at the char before it
+        let last = match source.content.get(start..end) {
+            Some(range) => start + range.char_indices().last().map_or(0, |(i, _)| i),
+            None => end,
+        };
+        let end_position = position(last, 3)?;
+
+        Ok(DebugLoc {
+            node_id,
+            loc: Loc {
+                start_line: start_position.line,
+                start_col: start_position.col,
+                end_line: end_position.line,
+                end_col: end_position.col,
+                source_file: source_file.clone(),
+            },
+            source_file,
+        })
+    }
 }
 
 impl<'a> From<&DebugLoc> for ratatui::prelude::Text<'a> {
@@ -81,6 +321,121 @@ impl<'a> From<&DebugLoc> for ratatui::prelude::Text<'a> {
 mod tests {
     use super::*;
 
+    fn span(start: (usize, usize), end: (usize, usize)) -> Span {
+        Span::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    #[test]
+    fn test_position_order() {
+        assert!(Position::new(3, 20) < Position::new(4, 2));
+        assert!(Position::new(4, 2) < Position::new(4, 3));
+        assert_eq!(
+            Position::new(4, 2).cmp(&Position::new(4, 2)),
+            std::cmp::Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn test_contains_point() {
+        let s = span((2, 5), (4, 3));
+        assert!(!s.contains_point(Position::new(1, 5)));
+        assert!(!s.contains_point(Position::new(2, 4)));
+        assert!(s.contains_point(Position::new(2, 5)));
+        assert!(s.contains_point(Position::new(3, 1)));
+        assert!(s.contains_point(Position::new(3, 100)));
+        assert!(s.contains_point(Position::new(4, 3)));
+        assert!(!s.contains_point(Position::new(4, 4)));
+        assert!(!s.contains_point(Position::new(5, 1)));
+        assert!(span((1, 7), (1, 7)).contains_point(Position::new(1, 7)));
+    }
+
+    #[test]
+    fn test_contains() {
+        let outer = span((3, 20), (6, 1));
+        // Starts on a later line but at a smaller column
+        assert!(outer.contains(&span((4, 2), (5, 30))));
+        assert!(outer.contains(&outer));
+        assert!(outer.contains(&span((3, 20), (3, 20))));
+        assert!(!outer.contains(&span((3, 19), (4, 1))));
+        assert!(!outer.contains(&span((5, 1), (6, 2))));
+        assert!(!span((4, 2), (5, 30)).contains(&outer));
+    }
+
+    #[test]
+    fn test_overlaps_and_intersection() {
+        let a = span((3, 20), (5, 4));
+        let b = span((5, 4), (7, 1));
+        let c = span((5, 5), (7, 1));
+        assert!(a.overlaps(&b) && b.overlaps(&a));
+        assert_eq!(a.intersection(&b), Some(span((5, 4), (5, 4))));
+        assert!(!a.overlaps(&c) && !c.overlaps(&a));
+        assert_eq!(a.intersection(&c), None);
+
+        let inner = span((4, 1), (4, 9));
+        assert!(a.overlaps(&inner));
+        assert_eq!(a.intersection(&inner), Some(inner));
+        assert_eq!(
+            span((1, 1), (4, 5)).intersection(&span((2, 8), (9, 9))),
+            Some(span((2, 8), (4, 5)))
+        );
+        assert_eq!(a.to_string(), "3:20-5:4");
+    }
+
+    fn convention(base: usize, end_inclusive: bool, column_unit: ColumnUnit) -> LocConvention {
+        LocConvention {
+            line_base: base,
+            column_base: base,
+            end_inclusive,
+            column_unit,
+        }
+    }
+
+    fn normalized(
+        loc: &str,
+        convention: &LocConvention,
+        lines: &[&str],
+    ) -> (usize, usize, usize, usize) {
+        let mut loc = Loc::from_str(loc).unwrap();
+        loc.normalize(convention, lines);
+        (loc.start_line, loc.start_col, loc.end_line, loc.end_col)
+    }
+
+    #[test]
+    fn test_normalize() {
+        // `b` is the 4th char, the 8th byte and the 5th UTF-16 unit
+        let lines = ["int a;", "é𝄞 b = a;"];
+        let default = LocConvention::default();
+        assert_eq!(normalized("2,4,2,4", &default, &[]), (2, 4, 2, 4));
+
+        let clang = convention(1, true, ColumnUnit::Byte);
+        assert_eq!(normalized("2,8,2,8", &clang, &lines), (2, 4, 2, 4));
+        // Inside the 4 bytes of the clef
+        assert_eq!(normalized("2,4,2,5", &clang, &lines), (2, 2, 2, 2));
+
+        let java = convention(0, false, ColumnUnit::Utf16);
+        assert_eq!(normalized("1,4,1,5", &java, &lines), (2, 4, 2, 4));
+        assert_eq!(normalized("0,0,1,3", &java, &lines), (1, 1, 2, 2));
+
+        let chars = convention(0, false, ColumnUnit::Char);
+        assert_eq!(normalized("1,3,1,4", &chars, &lines), (2, 4, 2, 4));
+        // Past the end of the line
+        assert_eq!(normalized("0,6,0,8", &chars, &lines), (1, 7, 1, 8));
+    }
+
+    #[test]
+    fn test_loc_containment() {
+        let outer = Loc::from_str("3,20,6,1").unwrap();
+        let inner = Loc::from_str("4,2,5,30").unwrap();
+        assert!(outer.contains(&inner));
+        assert!(inner.contained_by(&outer));
+        assert!(!outer.contained_by(&inner));
+        let elsewhere = Loc {
+            source_file: String::from("b.c"),
+            ..inner.clone()
+        };
+        assert!(!outer.contains(&elsewhere));
+    }
+
     #[test]
     fn test_parse_loc() {
         let line = "27,12,28,13";
@@ -101,4 +456,82 @@ mod tests {
             "tests/clang/evaluation/src/arena/test1.c"
         );
     }
+
+    #[test]
+    fn test_parse_debug_loc_errors() {
+        let err = DebugLoc::from_str("abc,test1.c,27,12,28,13").unwrap_err();
+        assert_eq!(err.to_string(), "column 0 `abc` is not a node id");
+
+        let err = DebugLoc::from_str("46,test1.c,27,12,x,13").unwrap_err();
+        assert_eq!(err.column, 4);
+
+        let err = DebugLoc::from_str("46,test1.c,27,12").unwrap_err();
+        assert_eq!(err.column, 4);
+    }
+
+    #[test]
+    fn test_parse_debug_loc_quoted() {
+        let line = r#"46,"src/a, b/""weird"".c",27,12,28,13"#;
+        let debug_loc = DebugLoc::from_str(line).unwrap();
+        assert_eq!(debug_loc.source_file, r#"src/a, b/"weird".c"#);
+        assert_eq!(debug_loc.loc.start_line, 27);
+        assert_eq!(debug_loc.loc.end_col, 13);
+    }
+
+    #[test]
+    fn test_offset_record() {
+        let source = SourceFile::new("a.c", String::from("int a;\né = f(x);\n"));
+        let parse = |line: &str| {
+            DebugLoc::from_offset_record(&csv_file::parse_line(line, b','), Some(&source))
+                .map(|d| d.loc.span().to_string())
+                .map_err(|e| e.column)
+        };
+        assert_eq!(parse("1,a.c,12,16"), Ok(String::from("2:5-2:8")));
+        // Across the end of the first line
+        assert_eq!(parse("1,a.c,4,9"), Ok(String::from("1:5-2:1")));
+        assert_eq!(parse("1,a.c,7,7"), Ok(String::from("2:1-2:1")));
+        assert_eq!(parse("1,a.c,8,9"), Err(2));
+        assert_eq!(parse("1,a.c,7,8"), Err(3));
+        assert_eq!(parse("1,a.c,9,8"), Err(3));
+        assert_eq!(parse("1,a.c,4,99"), Err(3));
+        assert_eq!(parse("1,a.c,4"), Err(3));
+
+        let record = csv_file::parse_line("1,a.c,1,2", b',');
+        assert_eq!(
+            DebugLoc::from_offset_record(&record, None)
+                .unwrap_err()
+                .column,
+            1
+        );
+    }
+
+    #[test]
+    fn test_from_columns() {
+        let point = LocationColumns {
+            file: 1,
+            line: 2,
+            col: 3,
+            end_line: None,
+            end_col: None,
+        };
+        let record = csv_file::parse_line("x,a.c,4,7,5,2", b',');
+        let loc = Loc::from_columns(&record, &point).unwrap();
+        assert_eq!(
+            (loc.source_file.as_str(), loc.span().to_string()),
+            ("a.c", String::from("4:7-4:7"))
+        );
+
+        let span = LocationColumns {
+            end_line: Some(4),
+            end_col: Some(5),
+            ..point.clone()
+        };
+        let loc = Loc::from_columns(&record, &span).unwrap();
+        assert_eq!(loc.span().to_string(), "4:7-5:2");
+
+        let record = csv_file::parse_line("x,a.c,4,y", b',');
+        assert_eq!(Loc::from_columns(&record, &point).unwrap_err().column, 3);
+        let record = csv_file::parse_line("x,a.c,4,7", b',');
+        assert_eq!(Loc::from_columns(&record, &span).unwrap_err().column, 4);
+    }
 }
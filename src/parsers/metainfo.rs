@@ -1,149 +1,781 @@
-use std::{collections::HashMap, io::BufRead, rc::Rc, str::FromStr};
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+    rc::Rc,
+};
+
+use csv::StringRecord;
 
 use super::{
-    analysis::Tuple, csv_file::RawAnalysis, debug_file::DebugJson, lib::SourceFile,
-    loc_file::DebugLoc,
+    analysis::Tuple,
+    csv_file::{self, RawAnalysis},
+    debug_file::{Column, DebugJson, LocConvention, LocFormat},
+    error::{FieldError, LoadError},
+    interval_tree::IntervalTree,
+    lib::SourceFile,
+    loc_file::{DebugLoc, Loc, Position},
 };
 
+/// Something with a span in the source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Located {
+    /// A node id from a loc file
+    Node(usize),
+    /// A location given by the columns of a row: the index of its tuple in `analyses` and of
+    /// the location in the tuple's `locations`
+    Tuple(usize, usize),
+}
+
 #[derive(Debug)]
 pub struct MetaInfo {
+    /// The directory holding `debug.json`, as given to `new`
+    pub root: String,
     pub source_files: HashMap<String, Rc<SourceFile>>,
     pub debug_json: Vec<DebugJson>,
     pub analyses: Vec<RawAnalysis>,
     pub debug_locs: Vec<DebugLoc>,
+    /// Everything that could not be loaded
+    pub problems: Vec<LoadError>,
+    /// Node id to index in `debug_locs`
+    loc_index: HashMap<usize, usize>,
+    /// Node id to indices in `analyses` of the tuples mentioning it
+    tuple_index: HashMap<usize, Vec<usize>>,
+    /// Relation name to its tuples in `analyses`
+    relation_index: HashMap<String, Range<usize>>,
+    /// Relation name to its column schema, for relations that have one
+    columns: HashMap<String, Rc<Vec<Column>>>,
+    /// Source file to the spans of the nodes and row locations in it
+    span_index: HashMap<String, IntervalTree<Position, Located>>,
+    /// Source file to the number of tuples per relation with a location in it
+    file_index: HashMap<String, HashMap<String, usize>>,
+}
+
+/// Open `file` (relative to `root`) and hand every record to `f`. Records that `f` rejects are
+/// skipped and, like a file that cannot be read, reported in `problems`.
+fn for_each_record(
+    root: &str,
+    file: &str,
+    delimiter: u8,
+    problems: &mut Vec<LoadError>,
+    mut f: impl FnMut(&StringRecord) -> Result<(), FieldError>,
+) {
+    let io_error = |source| LoadError::Io {
+        file: file.to_owned(),
+        source,
+    };
+    let mut reader = match std::fs::File::open(format!("{}/{}", root, file)) {
+        Ok(file) => csv_file::reader(std::io::BufReader::new(file), delimiter),
+        Err(e) => {
+            problems.push(io_error(e));
+            return;
+        }
+    };
+    for record in reader.records() {
+        let result = match record {
+            Ok(record) if record.iter().all(|field| field.trim().is_empty()) => continue,
+            Ok(record) => f(&record).map_err(|error| (record.position().cloned(), error)),
+            Err(e) => match e.kind() {
+                csv::ErrorKind::Utf8 { pos, err } => Err((
+                    pos.clone(),
+                    FieldError::new(err.field(), "is not valid UTF-8"),
+                )),
+                _ => {
+                    problems.push(io_error(std::io::Error::other(e)));
+                    return;
+                }
+            },
+        };
+        if let Err((pos, error)) = result {
+            problems.push(LoadError::Row {
+                file: file.to_owned(),
+                line: pos.map(|p| p.line() as usize).unwrap_or(0),
+                error,
+            });
+        }
+    }
+}
+
+/// The source `file` (relative to `root`), read on first use. A file that cannot be read is
+/// remembered in `unreadable` and not tried again.
+fn load_source(
+    root: &str,
+    file: &str,
+    source_files: &mut HashMap<String, Rc<SourceFile>>,
+    unreadable: &mut Vec<(String, std::io::Error)>,
+) -> Option<Rc<SourceFile>> {
+    if let Some(source_file) = source_files.get(file) {
+        return Some(source_file.clone());
+    }
+    if unreadable.iter().any(|(f, _)| f == file) {
+        return None;
+    }
+
+    match SourceFile::new_from_path(file, format!("{}/{}", root, file).as_str()) {
+        Ok(source_file) => {
+            let source_file = Rc::from(source_file);
+            source_files.insert(file.to_owned(), Rc::clone(&source_file));
+            Some(source_file)
+        }
+        Err(source) => {
+            unreadable.push((file.to_owned(), source));
+            None
+        }
+    }
+}
+
+/// Convert `locs` from `convention` to the one metavis uses, see `Loc::normalize`
+fn normalize<'a>(
+    locs: impl Iterator<Item = &'a mut Loc>,
+    convention: &LocConvention,
+    source_files: &HashMap<String, Rc<SourceFile>>,
+) {
+    if *convention == LocConvention::default() {
+        return;
+    }
+    let mut lines: HashMap<String, Vec<&str>> = HashMap::new();
+    for loc in locs {
+        if !lines.contains_key(&loc.source_file) {
+            let content = source_files
+                .get(&loc.source_file)
+                .map(|s| s.content.lines().collect())
+                .unwrap_or_default();
+            lines.insert(loc.source_file.clone(), content);
+        }
+        loc.normalize(convention, &lines[&loc.source_file]);
+    }
 }
 
 impl MetaInfo {
-    pub fn new(root: &str) -> Self {
-        let json = std::fs::read_to_string(format!("{}/debug.json", root)).unwrap();
-        let debug_json = serde_json::from_str::<Vec<DebugJson>>(&json).unwrap();
+    /// Load everything that `debug.json` in `root` refers to. Only a missing or invalid
+    /// `debug.json` is fatal, every other problem is collected in `problems`.
+    pub fn new(root: &str) -> Result<Self, LoadError> {
+        let json = std::fs::read_to_string(format!("{}/debug.json", root)).map_err(|source| {
+            LoadError::Io {
+                file: String::from("debug.json"),
+                source,
+            }
+        })?;
+        let mut debug_json =
+            serde_json::from_str::<Vec<DebugJson>>(&json).map_err(|source| LoadError::Json {
+                file: String::from("debug.json"),
+                source,
+            })?;
+        let mut problems = vec![];
+        // How the first relation using each loc file reads it, the others have to agree
+        let mut loc_files: HashMap<String, (LocFormat, LocConvention)> = HashMap::new();
+        // A relation that is not valid is left out
+        debug_json.retain_mut(|d| {
+            let result = d.validate().and_then(|()| {
+                let Some(loc_file) = &d.loc_file else {
+                    return Ok(());
+                };
+                let (format, convention) = *loc_files
+                    .entry(loc_file.clone())
+                    .or_insert((d.loc_format, d.loc_convention));
+                // Byte offsets do not depend on the convention
+                let field = if format != d.loc_format {
+                    "locFormat"
+                } else if format == LocFormat::LineColumn && convention != d.loc_convention {
+                    "locConvention"
+                } else {
+                    return Ok(());
+                };
+                Err(format!(
+                    "{} differs from another relation using {}",
+                    field, loc_file
+                ))
+            });
+            match result {
+                Ok(()) => true,
+                Err(message) => {
+                    problems.push(LoadError::Schema {
+                        file: String::from("debug.json"),
+                        relation: d.name.clone(),
+                        message,
+                    });
+                    false
+                }
+            }
+        });
         let mut analyses = vec![];
         let mut source_files: HashMap<String, Rc<SourceFile>> = HashMap::new();
-        let mut debug_loc_files: Vec<&str> = vec![];
+        // The first relation using each loc file
+        let mut debug_loc_files: Vec<(&str, &DebugJson)> = vec![];
         let mut debug_locs: Vec<DebugLoc> = vec![];
+        let mut unreadable_sources = vec![];
 
         for d in &debug_json {
-            let file = std::fs::File::open(format!("{}/{}", root, d.file)).unwrap();
-            let reader = std::io::BufReader::new(file);
-            for line in reader.lines() {
-                let analysis = RawAnalysis::new(&d.fields_with_nodes, &d.name, &line.unwrap());
+            let first = analyses.len();
+            // A wrong number of columns tends to affect every row, so only the first such row
+            // is reported, along with how many there were
+            let mut miscounted: Option<(usize, FieldError)> = None;
+            let mut miscounted_rows = 0;
+            for_each_record(root, &d.file, d.delimiter, &mut problems, |record| {
+                if let Err(error) = d.check_length(record) {
+                    let line = record.position().map_or(0, |p| p.line() as usize);
+                    miscounted.get_or_insert((line, error));
+                    miscounted_rows += 1;
+                    return Ok(());
+                }
+                d.check_record(record)?;
+                let mut analysis = RawAnalysis::new(&d.fields_with_nodes, &d.name, record)?;
+                for columns in &d.locations {
+                    let loc = Loc::from_columns(record, columns)?;
+                    load_source(
+                        root,
+                        &loc.source_file,
+                        &mut source_files,
+                        &mut unreadable_sources,
+                    );
+                    analysis.locations.push((columns.columns(), loc));
+                }
                 analyses.push(analysis);
+                Ok(())
+            });
+            if let Some((line, mut error)) = miscounted {
+                if miscounted_rows > 1 {
+                    error.message += &format!(" ({} rows were skipped)", miscounted_rows);
+                }
+                problems.push(LoadError::Row {
+                    file: d.file.clone(),
+                    line,
+                    error,
+                });
             }
+            let locs = analyses[first..]
+                .iter_mut()
+                .flat_map(|a| a.locations.iter_mut().map(|(_, loc)| loc));
+            normalize(locs, &d.loc_convention, &source_files);
 
-            debug_loc_files.push(&d.loc_file);
+            if let Some(loc_file) = &d.loc_file {
+                if !debug_loc_files.iter().any(|(f, _)| f == loc_file) {
+                    debug_loc_files.push((loc_file, d));
+                }
+            }
         }
 
-        debug_loc_files.dedup();
-
-        for d in &debug_loc_files {
-            let file = std::fs::File::open(format!("{}/{}", root, d)).unwrap();
-            let reader = std::io::BufReader::new(file);
-            for line in reader.lines() {
-                let loc = DebugLoc::from_str(&line.unwrap()).unwrap();
-
-                if !source_files.contains_key(&loc.source_file) {
-                    let source_file = SourceFile::new_from_path(
-                        &loc.source_file,
-                        format!("{}/{}", root, loc.source_file).as_str(),
-                    );
-                    if let Ok(source_file) = source_file {
-                        source_files.insert(loc.source_file.clone(), Rc::from(source_file));
+        for (loc_file, d) in &debug_loc_files {
+            let first = debug_locs.len();
+            for_each_record(root, loc_file, d.loc_delimiter, &mut problems, |record| {
+                let mut load = |file: &str| {
+                    load_source(root, file, &mut source_files, &mut unreadable_sources)
+                };
+                let loc = match d.loc_format {
+                    LocFormat::LineColumn => {
+                        let loc = DebugLoc::from_record(record)?;
+                        load(&loc.source_file);
+                        loc
                     }
-                }
+                    LocFormat::ByteOffset => {
+                        let source = record.get(1).and_then(load);
+                        DebugLoc::from_offset_record(record, source.as_deref())?
+                    }
+                };
 
                 debug_locs.push(loc);
+                Ok(())
+            });
+
+            if d.loc_format == LocFormat::LineColumn {
+                let locs = debug_locs[first..].iter_mut().map(|d| &mut d.loc);
+                normalize(locs, &d.loc_convention, &source_files);
             }
         }
+        problems.extend(
+            unreadable_sources
+                .into_iter()
+                .map(|(file, source)| LoadError::Io { file, source }),
+        );
 
-        // source_files.iter_mut().for_each(|(k, v)| {
-        //     *v = Rc::from(std::fs::read_to_string(format!("{}/{}", root, k)).unwrap())
-        // });
+        let located = debug_locs.iter().map(|d| d.node_id).collect::<HashSet<_>>();
+        let mut unlocated: HashMap<&str, Vec<usize>> = HashMap::new();
+        for a in &analyses {
+            for n in a.nodes.iter().filter(|n| !located.contains(n)) {
+                unlocated.entry(&a.name).or_default().push(*n);
+            }
+        }
+        for d in &debug_json {
+            if let Some(mut nodes) = unlocated.remove(d.name.as_str()) {
+                nodes.sort();
+                nodes.dedup();
+                problems.push(LoadError::Unlocated {
+                    file: d.file.clone(),
+                    nodes,
+                });
+            }
+        }
 
-        Self {
+        let mut metainfo = Self {
+            root: root.to_owned(),
             source_files,
             debug_json,
             analyses,
             debug_locs,
-        }
+            problems,
+            loc_index: HashMap::new(),
+            tuple_index: HashMap::new(),
+            relation_index: HashMap::new(),
+            columns: HashMap::new(),
+            span_index: HashMap::new(),
+            file_index: HashMap::new(),
+        };
+        metainfo.build_indexes();
+
+        Ok(metainfo)
     }
 
-    // TODO: Make more performant
-    pub fn get_analyses(&self, nodes: &[usize]) -> Vec<Tuple> {
-        let raw_analyses = self
-            .analyses
-            .iter()
-            .filter(|d| nodes.iter().any(|n| d.nodes.contains(n)))
-            .collect::<Vec<_>>();
+    /// Build the lookup tables used by the queries below. `analyses` is reordered so that
+    /// every relation occupies a contiguous range.
+    fn build_indexes(&mut self) {
+        let mut order: HashMap<&str, usize> = HashMap::new();
+        for d in &self.debug_json {
+            let n = order.len();
+            order.entry(&d.name).or_insert(n);
+        }
+        self.analyses.sort_by_key(|a| order[a.name.as_str()]);
 
-        let debug_locs = self
-            .debug_locs
-            .iter()
-            .filter(|d| raw_analyses.iter().any(|n| n.nodes.contains(&d.node_id)))
-            .collect::<Vec<_>>();
+        for d in &self.debug_json {
+            if let Some(columns) = &d.columns {
+                self.columns
+                    .entry(d.name.clone())
+                    .or_insert_with(|| Rc::new(columns.clone()));
+            }
+        }
 
-        let mut tuples = vec![];
-        for d in raw_analyses {
-            let locs = d
+        for (i, a) in self.analyses.iter().enumerate() {
+            match self.relation_index.get_mut(&a.name) {
+                Some(range) => range.end = i + 1,
+                None => {
+                    self.relation_index.insert(a.name.clone(), i..i + 1);
+                }
+            }
+
+            let mut nodes = a.nodes.clone();
+            nodes.sort();
+            nodes.dedup();
+            for n in nodes {
+                self.tuple_index.entry(n).or_default().push(i);
+            }
+        }
+
+        let mut spans: HashMap<&str, Vec<_>> = HashMap::new();
+        for (i, d) in self.debug_locs.iter().enumerate() {
+            // The first loc of a node wins, same as the linear search this replaced
+            if self.loc_index.contains_key(&d.node_id) {
+                continue;
+            }
+            self.loc_index.insert(d.node_id, i);
+            spans.entry(&d.source_file).or_default().push((
+                d.loc.start(),
+                d.loc.end(),
+                Located::Node(d.node_id),
+            ));
+        }
+        for (i, a) in self.analyses.iter().enumerate() {
+            for (j, (_, loc)) in a.locations.iter().enumerate() {
+                spans.entry(&loc.source_file).or_default().push((
+                    loc.start(),
+                    loc.end(),
+                    Located::Tuple(i, j),
+                ));
+            }
+        }
+        self.span_index = spans
+            .into_iter()
+            .map(|(file, spans)| (file.to_owned(), IntervalTree::new(spans)))
+            .collect();
+
+        for a in &self.analyses {
+            let mut files = a
                 .nodes
                 .iter()
-                .map(|n| match debug_locs.iter().find(|l| l.node_id == *n) {
-                    Some(l) => l.loc.clone(),
-                    None => panic!("node not found in debug locs"), // TODO: Fix this
-                })
-                .collect();
+                .filter_map(|n| self.loc_index.get(n))
+                .map(|i| &self.debug_locs[*i].source_file)
+                .chain(a.locations.iter().map(|(_, loc)| &loc.source_file))
+                .collect::<Vec<_>>();
+            files.sort();
+            files.dedup();
+            for file in files {
+                *self
+                    .file_index
+                    .entry(file.clone())
+                    .or_default()
+                    .entry(a.name.clone())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// The column schema of `relation`, if `debug.json` declares one
+    pub fn get_columns(&self, relation: &str) -> Option<&[Column]> {
+        self.columns.get(relation).map(|c| c.as_slice())
+    }
 
-            let d = Tuple::new(d.name.clone(), d.nodes.clone(), locs);
+    pub fn get_loc(&self, node: usize) -> Option<&DebugLoc> {
+        self.loc_index.get(&node).map(|i| &self.debug_locs[*i])
+    }
+
+    /// `node` is mentioned by a tuple or has a location
+    pub fn has_node(&self, node: usize) -> bool {
+        self.tuple_index.contains_key(&node) || self.loc_index.contains_key(&node)
+    }
+
+    /// Every node id with a location, in ascending order
+    pub fn node_ids(&self) -> Vec<usize> {
+        let mut ids = self.loc_index.keys().copied().collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
 
-            tuples.push(d);
+    /// The span of `located`
+    pub fn get_span(&self, located: Located) -> Option<&Loc> {
+        match located {
+            Located::Node(n) => self.get_loc(n).map(|d| &d.loc),
+            Located::Tuple(i, j) => self.analyses[i].locations.get(j).map(|(_, loc)| loc),
         }
+    }
 
-        tuples
+    /// The node id of `located`, or the relation of the row it is in
+    pub fn label(&self, located: Located) -> String {
+        match located {
+            Located::Node(n) => n.to_string(),
+            Located::Tuple(i, _) => self.analyses[i].name.clone(),
+        }
     }
 
-    // TODO: Make more performant
-    pub fn get_tuples_for_relation(&self, relation: &str) -> Vec<Tuple> {
-        let raw_analyses = self
-            .analyses
-            .iter()
-            .filter(|d| d.name == relation)
+    /// Whether `tuple` mentions the node of `located`, or is the row it is in
+    pub fn touches(&self, tuple: &Tuple, located: Located) -> bool {
+        match located {
+            Located::Node(n) => tuple.nodes.contains(&n),
+            Located::Tuple(i, _) => {
+                let a = &self.analyses[i];
+                tuple.name == a.name && tuple.fields == a.fields
+            }
+        }
+    }
+
+    /// Nodes and row locations in `file` whose span contains `position`, innermost first
+    pub fn nodes_at(&self, file: &str, position: Position) -> Vec<Located> {
+        let Some(tree) = self.span_index.get(file) else {
+            return vec![];
+        };
+        let mut nodes = tree
+            .query(position)
+            .into_iter()
+            .filter_map(|n| Some((self.get_span(*n)?.span(), *n)))
             .collect::<Vec<_>>();
+        // Smallest span first, as lines and then columns. Of two spans around the same position
+        // the inner one is never larger, equal ones are ordered by the later start.
+        nodes.sort_by_key(|(span, n)| {
+            let lines = span.end.line - span.start.line;
+            let cols = span.end.col as isize - span.start.col as isize;
+            (lines, cols, std::cmp::Reverse(span.start), *n)
+        });
+        nodes.into_iter().map(|(_, n)| n).collect()
+    }
 
-        let debug_locs = self
-            .debug_locs
+    /// Number of tuples with a location in `file`, only counting `relation` if given
+    pub fn tuple_count(&self, file: &str, relation: Option<&str>) -> usize {
+        let Some(counts) = self.file_index.get(file) else {
+            return 0;
+        };
+        match relation {
+            Some(relation) => counts.get(relation).copied().unwrap_or(0),
+            None => counts.values().sum(),
+        }
+    }
+
+    fn make_tuple(&self, analysis: &RawAnalysis) -> Tuple {
+        let locs = analysis
+            .nodes
             .iter()
-            .filter(|d| raw_analyses.iter().any(|n| n.nodes.contains(&d.node_id)))
-            .collect::<Vec<_>>();
+            .map(|n| self.get_loc(*n).map(|l| l.loc.clone()))
+            .collect();
 
-        let mut tuples = vec![];
-        for d in raw_analyses {
-            let locs = d
-                .nodes
-                .iter()
-                .map(|n| match debug_locs.iter().find(|l| l.node_id == *n) {
-                    Some(l) => l.loc.clone(),
-                    None => panic!("node not found in debug locs"), // TODO: Fix this
-                })
-                .collect();
+        Tuple::new(analysis.name.clone(), analysis.fields.clone(), locs)
+            .with_locations(analysis.locations.clone())
+            .with_columns(self.columns.get(&analysis.name).cloned())
+    }
 
-            let d = Tuple::new(d.name.clone(), d.nodes.clone(), locs);
+    /// All tuples that mention any of the nodes of `nodes` or hold one of its row locations,
+    /// grouped by relation in the order of `debug.json` and in file order within a relation
+    pub fn get_analyses(&self, nodes: &[Located]) -> Vec<Tuple> {
+        let mut indices = nodes
+            .iter()
+            .flat_map(|n| match n {
+                Located::Node(n) => self.tuple_index.get(n).map_or(&[][..], |t| t.as_slice()),
+                Located::Tuple(i, _) => std::slice::from_ref(i),
+            })
+            .copied()
+            .collect::<Vec<_>>();
+        indices.sort();
+        indices.dedup();
 
-            tuples.push(d);
-        }
+        indices
+            .into_iter()
+            .map(|i| self.make_tuple(&self.analyses[i]))
+            .collect()
+    }
 
-        tuples
+    pub fn get_tuples_for_relation(&self, relation: &str) -> Vec<Tuple> {
+        match self.relation_index.get(relation) {
+            Some(range) => self.analyses[range.clone()]
+                .iter()
+                .map(|a| self.make_tuple(a))
+                .collect(),
+            None => vec![],
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_util::write_root;
 
     #[test]
     fn test_parse_meta_info() {
-        let meta_info = MetaInfo::new("./example_data");
+        let meta_info = MetaInfo::new("./example_data").unwrap();
         assert!(!meta_info.source_files.is_empty());
         assert!(!meta_info.debug_json.is_empty());
         assert!(!meta_info.analyses.is_empty());
         dbg!(meta_info.source_files);
     }
+
+    #[test]
+    fn test_indexes() {
+        let meta_info = MetaInfo::new("./example_data").unwrap();
+
+        let tuples = meta_info.get_tuples_for_relation("Call");
+        assert_eq!(tuples.len(), 4);
+        assert_eq!(tuples[0].nodes, vec![14, 46, 58]);
+        assert!(meta_info.get_tuples_for_relation("Nope").is_empty());
+
+        let tuples = meta_info.get_analyses(&[Located::Node(14)]);
+        assert_eq!(tuples.len(), 2);
+        assert!(tuples.iter().all(|t| t.name == "Call"));
+
+        assert_eq!(meta_info.get_loc(14).unwrap().loc.start_line, 27);
+        let file = "tests/clang/evaluation/src/arena/test1.c";
+        assert!(meta_info
+            .nodes_at(file, Position::new(27, 5))
+            .contains(&Located::Node(14)));
+        assert!(!meta_info
+            .nodes_at(file, Position::new(27, 17))
+            .contains(&Located::Node(14)));
+        assert_eq!(
+            meta_info.nodes_at(file, Position::new(27, 12)),
+            vec![Located::Node(46), Located::Node(14)]
+        );
+        assert!(meta_info
+            .nodes_at("other.c", Position::new(27, 5))
+            .is_empty());
+        assert!(meta_info.has_node(14));
+        assert!(!meta_info.has_node(999_999));
+        let ids = meta_info.node_ids();
+        assert!(ids.contains(&14));
+        assert!(ids.windows(2).all(|w| w[0] < w[1]));
+
+        assert_eq!(meta_info.tuple_count(file, Some("Call")), 4);
+        assert!(meta_info.tuple_count(file, None) > 4);
+        assert_eq!(meta_info.tuple_count(file, Some("Nope")), 0);
+        assert_eq!(meta_info.tuple_count("other.c", None), 0);
+    }
+
+    #[test]
+    fn test_nodes_at_order() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[{"name": "A", "file": "A.csv", "locs": [0], "locFile": "Loc.csv"}]"#,
+            ),
+            ("A.csv", "1\n2\n3\n"),
+            // 1 and 2 overlap without one being inside the other
+            ("Loc.csv", "1,a.c,1,2,1,4\n2,a.c,1,3,1,20\n3,a.c,1,1,2,1\n"),
+            ("a.c", "abcdefghijklmnopqrstuvwxyz\nb\n"),
+        ]);
+
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        assert_eq!(
+            meta_info.nodes_at("a.c", Position::new(1, 3)),
+            vec![Located::Node(1), Located::Node(2), Located::Node(3)]
+        );
+    }
+
+    #[test]
+    fn test_partial_load() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[
+                    {"name": "A", "file": "A.csv", "locs": [0], "locFile": "Loc.csv"},
+                    {"name": "B", "file": "B.csv", "locs": [0], "locFile": "Loc.csv"}
+                ]"#,
+            ),
+            ("A.csv", "1\nx\n2\n"),
+            ("Loc.csv", "1,a.c,1,1,1,2\n2,missing.c,1,1,1,2\n"),
+            ("a.c", "int a;\n"),
+        ]);
+
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        assert_eq!(meta_info.analyses.len(), 2);
+        assert_eq!(meta_info.debug_locs.len(), 2);
+        assert!(meta_info.source_files.contains_key("a.c"));
+        // B.csv, A.csv:2 and missing.c
+        assert_eq!(meta_info.problems.len(), 3, "{:?}", meta_info.problems);
+        assert!(meta_info
+            .problems
+            .iter()
+            .any(|p| matches!(p, LoadError::Row { file, line: 2, .. } if file == "A.csv")));
+    }
+
+    #[test]
+    fn test_schema_problems() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[
+                    {"name": "A", "file": "A.csv", "columns": [{"name": "a"}, {"name": "b"}]},
+                    {"name": "B", "file": "B.csv", "locs": [3], "columns": [{"name": "a"}]}
+                ]"#,
+            ),
+            ("A.csv", "x\ny\nz,w\nv\n"),
+        ]);
+
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        assert_eq!(meta_info.debug_json.len(), 1);
+        assert_eq!(meta_info.analyses.len(), 1);
+        assert_eq!(meta_info.problems.len(), 2, "{:?}", meta_info.problems);
+        assert!(matches!(
+            &meta_info.problems[0],
+            LoadError::Schema { relation, .. } if relation == "B"
+        ));
+        let LoadError::Row { line, error, .. } = &meta_info.problems[1] else {
+            panic!("{:?}", meta_info.problems[1]);
+        };
+        assert_eq!(*line, 1);
+        assert!(
+            error.message.ends_with("(3 rows were skipped)"),
+            "{}",
+            error.message
+        );
+    }
+
+    #[test]
+    fn test_loc_convention() {
+        let debug_json = |b_convention: &str| {
+            format!(
+                r#"[
+                    {{"name": "A", "file": "A.csv", "locs": [0], "locFile": "Loc.csv",
+                      "locConvention": {{"lineBase": 0, "columnBase": 0, "endInclusive": false,
+                                         "columnUnit": "byte"}}}},
+                    {{"name": "B", "file": "A.csv", "locs": [0], "locFile": "Loc.csv"{}}}
+                ]"#,
+                b_convention
+            )
+        };
+        let root = write_root(&[
+            ("debug.json", &debug_json("")),
+            ("A.csv", "1\n"),
+            // `f(x)` is bytes 5..9 and chars 4..8 of the second line
+            ("Loc.csv", "1,a.c,1,5,1,9\n"),
+            ("a.c", "int a;\né = f(x);\n"),
+        ]);
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+        assert_eq!(meta_info.debug_json.len(), 1);
+        assert_eq!(meta_info.get_tuples_for_relation("B").len(), 0);
+        assert!(matches!(
+            &meta_info.problems[..],
+            [LoadError::Schema { relation, .. }] if relation == "B"
+        ));
+
+        let same = r#", "locConvention": {"lineBase": 0, "columnBase": 0, "endInclusive": false,
+                                          "columnUnit": "byte"}"#;
+        std::fs::write(root.join("debug.json"), debug_json(same)).unwrap();
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        let loc = &meta_info.get_loc(1).unwrap().loc;
+        assert_eq!(loc.span().to_string(), "2:5-2:8");
+        assert_eq!(
+            meta_info.nodes_at("a.c", Position::new(2, 8)),
+            vec![Located::Node(1)]
+        );
+        assert!(meta_info.nodes_at("a.c", Position::new(2, 9)).is_empty());
+    }
+
+    #[test]
+    fn test_byte_offsets() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[{"name": "A", "file": "A.csv", "locs": [0], "locFile": "Loc.csv",
+                     "locFormat": "byteOffset"}]"#,
+            ),
+            ("A.csv", "1\n2\n3\n"),
+            // `f(x)` is bytes 12..16
+            ("Loc.csv", "1,a.c,12,16\n2,a.c,3,3\n3,a.c,4,40\n"),
+            ("a.c", "int a;\né = f(x);\n"),
+        ]);
+
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        let loc = &meta_info.get_loc(1).unwrap().loc;
+        assert_eq!(loc.span().to_string(), "2:5-2:8");
+        assert_eq!(
+            meta_info.nodes_at("a.c", Position::new(2, 8)),
+            vec![Located::Node(1)]
+        );
+        assert_eq!(
+            meta_info.get_loc(2).unwrap().loc.span().to_string(),
+            "1:4-1:4"
+        );
+        assert!(meta_info.get_loc(3).is_none());
+        assert!(meta_info
+            .problems
+            .iter()
+            .any(|p| p.to_string() == "Loc.csv:3: column 3 `40` is past the end of a.c"));
+    }
+
+    #[test]
+    fn test_inline_locations() {
+        let root = write_root(&[
+            (
+                "debug.json",
+                r#"[{"name": "Warning", "file": "Warning.csv",
+                     "locations": [{"file": 1, "line": 2, "col": 3, "endLine": 2, "endCol": 4}],
+                     "locConvention": {"columnBase": 0, "endInclusive": false}}]"#,
+            ),
+            (
+                "Warning.csv",
+                "unused,a.c,2,4,8\nshadowed,a.c,x,1,1\nunused,b.c,1,0,1\n",
+            ),
+            ("a.c", "int a;\né = f(x);\n"),
+        ]);
+
+        let meta_info = MetaInfo::new(root.to_str().unwrap()).unwrap();
+
+        let tuples = meta_info.get_tuples_for_relation("Warning");
+        assert_eq!(tuples.len(), 2);
+        assert!(tuples[0].nodes.is_empty());
+        assert_eq!(tuples[0].current().unwrap().span().to_string(), "2:5-2:8");
+        assert_eq!(meta_info.tuple_count("a.c", Some("Warning")), 1);
+        let located = meta_info.nodes_at("a.c", Position::new(2, 8));
+        assert_eq!(located, vec![Located::Tuple(0, 0)]);
+        assert!(meta_info.touches(&tuples[0], located[0]));
+        assert!(!meta_info.touches(&tuples[1], located[0]));
+        assert_eq!(meta_info.get_analyses(&located).len(), 1);
+        assert_eq!(meta_info.label(located[0]), "Warning");
+        assert!(meta_info.source_files.contains_key("a.c"));
+        // The row with a bad line and the unreadable file
+        assert_eq!(meta_info.problems.len(), 2);
+    }
+
+    #[test]
+    fn test_missing_root() {
+        let err = MetaInfo::new("./does_not_exist").unwrap_err();
+        assert!(matches!(err, LoadError::Io { ref file, .. } if file == "debug.json"));
+    }
 }
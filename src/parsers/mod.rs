@@ -1,10 +1,17 @@
 mod analysis;
 mod csv_file;
 mod debug_file;
+mod error;
+mod interval_tree;
 mod lib;
 mod loc_file;
 mod metainfo;
 pub type Loc = loc_file::Loc;
+pub type Position = loc_file::Position;
+pub type Span = loc_file::Span;
 pub type Tuple = analysis::Tuple;
 pub type Relation = analysis::Relation;
 pub type MetaInfo = metainfo::MetaInfo;
+pub type Located = metainfo::Located;
+pub type Field = csv_file::Field;
+pub type Column = debug_file::Column;
@@ -0,0 +1,99 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::jump_list::{Jump, JumpList};
+
+/// What was open and selected when metavis exited, restored on the next start
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct Session {
+    /// Open file, cursor, selected relation and tuple
+    pub position: Jump,
+    /// Scroll of the source view as (line, column)
+    pub scroll: (u16, u16),
+    pub filter: Option<String>,
+    pub jumps: JumpList,
+}
+
+/// The state file holding the sessions of every root
+pub fn default_path() -> Option<PathBuf> {
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|dir| dir.join("metavis").join("sessions.json"))
+}
+
+/// Sessions are keyed by the absolute path of the root
+fn key(root: &str) -> String {
+    std::fs::canonicalize(root)
+        .map(|p| p.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| root.to_owned())
+}
+
+fn read_all(file: &Path) -> io::Result<BTreeMap<String, Session>> {
+    match std::fs::read_to_string(file) {
+        Ok(content) => serde_json::from_str(&content).map_err(io::Error::other),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// The session saved in `file` for `root`, if any
+pub fn load(file: &Path, root: &str) -> io::Result<Option<Session>> {
+    Ok(read_all(file)?.remove(&key(root)))
+}
+
+/// Save `session` for `root` in `file`, keeping the sessions of other roots
+pub fn save(file: &Path, root: &str, session: Session) -> io::Result<()> {
+    // A file that cannot be read is left alone, it may hold sessions worth keeping
+    let mut sessions = read_all(file)?;
+    sessions.insert(key(root), session);
+
+    if let Some(dir) = file.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let content = serde_json::to_string_pretty(&sessions).map_err(io::Error::other)?;
+    std::fs::write(file, content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::write_root;
+
+    fn session(file: &str) -> Session {
+        Session {
+            position: Jump {
+                file: String::from(file),
+                cursor: (3, 26),
+                relation: Some(String::from("Call")),
+                tuple: None,
+            },
+            scroll: (10, 0),
+            filter: Some(String::from("node:14")),
+            jumps: JumpList::new(),
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = write_root(&[]);
+        let file = dir.join("state").join("sessions.json");
+
+        assert_eq!(load(&file, "./example_data").unwrap(), None);
+        save(&file, "./example_data", session("a.c")).unwrap();
+        save(&file, "./other", session("b.c")).unwrap();
+        // The same root through another path
+        let loaded = load(&file, "./example_data/../example_data").unwrap();
+        assert_eq!(loaded, Some(session("a.c")));
+        assert_eq!(load(&file, "./other").unwrap(), Some(session("b.c")));
+
+        std::fs::write(&file, "not json").unwrap();
+        assert!(load(&file, "./example_data").is_err());
+        assert!(save(&file, "./example_data", session("c.c")).is_err());
+        assert_eq!(std::fs::read_to_string(&file).unwrap(), "not json");
+    }
+}
@@ -1,19 +1,157 @@
 use ratatui::{
-    style::Style,
+    style::{Style, Stylize},
     text::{Line, Span},
     widgets::Paragraph,
 };
 
-use crate::parsers::Loc;
+use unicode_width::UnicodeWidthChar;
+
+use crate::parsers::{Loc, Position};
+
+/// Columns between tab stops unless `--tab-width` is given
+pub const TAB_WIDTH: usize = 8;
+
+/// Screen columns taken by `c` when it starts at screen column `col`, tabs go to the next stop
+fn char_width(c: char, col: usize, tab_width: usize) -> usize {
+    match c {
+        '\t' => tab_width - col % tab_width,
+        c => c.width().unwrap_or(0),
+    }
+}
+
+/// The 0-based screen column where the 1-based character column `col` of `line` starts.
+/// Columns past the end of the line take one screen column each.
+pub fn display_col(line: &str, col: usize, tab_width: usize) -> usize {
+    let before = col.saturating_sub(1);
+    let mut width = 0;
+    let mut chars = 0;
+    for c in line.chars().take(before) {
+        width += char_width(c, width, tab_width);
+        chars += 1;
+    }
+    width + before - chars
+}
+
+/// `line` as shown on screen, with tabs replaced by spaces
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut expanded = String::new();
+    let mut col = 0;
+    for c in line.chars() {
+        let width = char_width(c, col, tab_width);
+        match c {
+            '\t' => expanded.push_str(&" ".repeat(width)),
+            c => expanded.push(c),
+        }
+        col += width;
+    }
+    expanded
+}
+
+/// A match of a search in the source, on a single line
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Match {
+    /// 0-based line
+    pub line: usize,
+    /// Byte offsets into the line, `end` is exclusive
+    pub start: usize,
+    pub end: usize,
+}
+
+pub struct Search {
+    pub pattern: String,
+    /// Searching with `\` instead of `/`, `n` then moves backwards
+    pub backwards: bool,
+    /// Every match in the source, in order
+    matches: Vec<Match>,
+    /// Index in `matches` of the match that was jumped to last
+    current: Option<usize>,
+}
+
+impl Search {
+    pub fn new(pattern: &str, backwards: bool, content: &str) -> Result<Self, String> {
+        let regex = regex::Regex::new(pattern).map_err(|e| match e {
+            regex::Error::Syntax(s) => s.lines().last().unwrap_or_default().to_owned(),
+            e => e.to_string(),
+        })?;
+        let matches = content
+            .lines()
+            .enumerate()
+            .flat_map(|(line, text)| {
+                regex.find_iter(text).map(move |m| Match {
+                    line,
+                    start: m.start(),
+                    end: m.end(),
+                })
+            })
+            .collect();
+
+        Ok(Self {
+            pattern: pattern.to_owned(),
+            backwards,
+            matches,
+            current: None,
+        })
+    }
+
+    /// The matches on the 0-based `line`
+    pub fn matches_on(&self, line: usize) -> &[Match] {
+        let start = self.matches.partition_point(|m| m.line < line);
+        let end = self.matches.partition_point(|m| m.line <= line);
+        &self.matches[start..end]
+    }
+
+    pub fn is_current(&self, m: &Match) -> bool {
+        self.current.map(|c| &self.matches[c]) == Some(m)
+    }
+
+    /// Find the first match after (or before if `backwards`) `line` and byte offset `offset`,
+    /// wrapping around at the end of the source
+    fn find(&mut self, line: usize, offset: usize, backwards: bool) -> Option<Match> {
+        if self.matches.is_empty() {
+            return None;
+        }
+        let after = self
+            .matches
+            .partition_point(|m| (m.line, m.start) <= (line, offset));
+        let i = if backwards {
+            let before = self
+                .matches
+                .partition_point(|m| (m.line, m.start) < (line, offset));
+            (before + self.matches.len() - 1) % self.matches.len()
+        } else {
+            after % self.matches.len()
+        };
+        self.current = Some(i);
+
+        Some(self.matches[i])
+    }
+
+    /// `[current/total] /pattern`
+    pub fn status(&self) -> String {
+        let prefix = if self.backwards { '\\' } else { '/' };
+        match (self.matches.len(), self.current) {
+            (0, _) => format!("no matches {}{}", prefix, self.pattern),
+            (n, Some(c)) => format!("[{}/{}] {}{}", c + 1, n, prefix, self.pattern),
+            (n, None) => format!("[{}] {}{}", n, prefix, self.pattern),
+        }
+    }
+}
+
 pub struct SourceView {
     pub name: String,
     pub content: Option<String>,
     pub highlights: Vec<Loc>,
+    /// The node gone to by id, underlined on top of the highlights
+    pub node: Option<Loc>,
+    pub search: Option<Search>,
     /// Note: This is (y, x) and not (x, y)
     scroll: (u16, u16),
     /// (x, y)
     cursor: (u16, u16),
+    /// The column `Up` and `Down` keep to, which may be past the end of a shorter line
+    goal_col: u16,
     line_padding: usize,
+    pub tab_width: usize,
 }
 
 pub enum Direction {
@@ -29,9 +167,13 @@ impl SourceView {
             name: String::new(),
             content: None,
             highlights: Vec::new(),
+            node: None,
+            search: None,
             scroll: (0, 0),
-            cursor: (0, 0),
+            cursor: (1, 0),
+            goal_col: 1,
             line_padding: 0,
+            tab_width: TAB_WIDTH,
         }
     }
 
@@ -54,6 +196,13 @@ impl SourceView {
         let n_lines = source_lines.clone().count().to_string().len();
         self.line_padding = n_lines + 1;
         let mut lines = Vec::new();
+        // Tuples may have locations in other files too
+        let highlights = self
+            .highlights
+            .iter()
+            .filter(|h| h.source_file == self.name)
+            .map(|h| h.span())
+            .collect::<Vec<_>>();
 
         for (i, line) in source_lines.enumerate() {
             let line = line.to_owned();
@@ -61,20 +210,22 @@ impl SourceView {
             let line_no = Span::from(format!("{:>pad$} ", j, pad = self.line_padding - 1));
             let mut content = vec![line_no];
 
-            if self.highlights.is_empty() {
-                content.push(Span::raw(line));
-                lines.push(Line::from(content));
-                continue;
-            }
-
-            let highlights_for_line = self
-                .highlights
+            let highlights_for_line = highlights
                 .iter()
-                .filter(|h| h.start_line <= j && j <= h.end_line)
+                .filter(|s| s.lines().contains(&j))
                 .collect::<Vec<_>>();
+            let matches_for_line = match &self.search {
+                Some(search) => search.matches_on(i),
+                None => &[],
+            };
+            let node = self
+                .node
+                .as_ref()
+                .map(|n| n.span())
+                .filter(|s| s.lines().contains(&j));
 
-            if highlights_for_line.is_empty() {
-                content.push(Span::raw(line));
+            if highlights_for_line.is_empty() && matches_for_line.is_empty() && node.is_none() {
+                content.push(Span::raw(expand_tabs(&line, self.tab_width)));
                 lines.push(Line::from(content));
                 continue;
             }
@@ -82,35 +233,44 @@ impl SourceView {
             let mut acc = String::new();
 
             // TODO: Map highlight color to a node id (maybe)
-            let mut old_level = 0;
-            let mut level = 0;
-            for (k, c) in line.char_indices() {
-                let k = k + 1;
-                level = highlights_for_line
+            let mut old_style = Style::default();
+            // Screen column, for expanding tabs
+            let mut col = 0;
+            // Matches are in bytes and locs in chars
+            for (n, (k, c)) in line.char_indices().enumerate() {
+                let in_match = matches_for_line.iter().find(|m| m.start <= k && k < m.end);
+                let position = Position::new(j, n + 1);
+                let level = highlights_for_line
                     .iter()
-                    .filter(|h| {
-                        if h.start_line == h.end_line {
-                            h.start_col <= k && k <= h.end_col
-                        } else if h.start_line == j {
-                            h.start_col <= k
-                        } else if h.end_line == j {
-                            k <= h.end_col
-                        } else {
-                            true
-                        }
-                    })
-                    .collect::<Vec<_>>()
-                    .len();
-
-                if level != old_level {
-                    content.push(Span::styled(acc, Self::get_color(&old_level)));
+                    .filter(|s| s.contains_point(position))
+                    .count();
+
+                // The node and search matches are layered on top of the highlights
+                let mut style = Self::get_color(&level);
+                if node.is_some_and(|s| s.contains_point(position)) {
+                    style = style.fg(ratatui::style::Color::Magenta).underlined();
+                }
+                if let Some(m) = in_match {
+                    style = style.reversed();
+                    if self.search.as_ref().is_some_and(|s| s.is_current(m)) {
+                        style = style.bold().underlined();
+                    }
+                }
+
+                if style != old_style {
+                    content.push(Span::styled(acc, old_style));
                     acc = String::new();
-                    old_level = level;
+                    old_style = style;
                 }
 
-                acc.push(c);
+                let width = char_width(c, col, self.tab_width);
+                match c {
+                    '\t' => acc.push_str(&" ".repeat(width)),
+                    c => acc.push(c),
+                }
+                col += width;
             }
-            content.push(Span::styled(acc, Self::get_color(&level)));
+            content.push(Span::styled(acc, old_style));
 
             lines.push(Line::from(content));
         }
@@ -120,68 +280,84 @@ impl SourceView {
         p.scroll(self.scroll)
     }
 
-    pub fn move_to(&mut self, mut target: (u16, u16)) {
+    /// Move the cursor to (column, line), kept on a character of an existing line. The column is
+    /// 1-based in characters and the line 0-based
+    pub fn move_to(&mut self, (col, line): (u16, u16)) {
         if let Some(c) = &self.content {
-            target.1 = target.1.min(c.lines().count() as u16 - 1);
-            self.cursor = target;
+            let line = line.min(c.lines().count().saturating_sub(1) as u16);
+            let len = c
+                .lines()
+                .nth(line as usize)
+                .map_or(0, |l| l.chars().count());
+            let len = len.clamp(1, u16::MAX.into()) as u16;
+            self.cursor = (col.clamp(1, len), line);
+            self.goal_col = self.cursor.0;
         }
     }
+
     pub fn move_to_start(&mut self) {
-        self.move_to((0, 0));
+        self.move_to((1, 0));
     }
 
     pub fn move_to_end(&mut self) {
-        if let Some(c) = &self.content {
-            let lines = c.lines().count() as u16;
-            self.cursor = (0, lines - 1);
+        self.move_to((1, u16::MAX));
+    }
+
+    /// Start a new search and jump to the first match from the cursor
+    pub fn search(&mut self, pattern: &str, backwards: bool) -> Result<(), String> {
+        let Some(content) = &self.content else {
+            return Err(String::from("no file to search"));
+        };
+        self.search = Some(Search::new(pattern, backwards, content)?);
+        self.search_next(false);
+
+        Ok(())
+    }
+
+    /// Jump to the next match in the direction of the search, or the opposite if `reverse`
+    pub fn search_next(&mut self, reverse: bool) {
+        let (Some(search), Some(content)) = (&mut self.search, &self.content) else {
+            return;
+        };
+        let line = self.cursor.1 as usize;
+        let text = content.lines().nth(line).unwrap_or_default();
+        // The cursor column is 1-based in characters, matches are in bytes
+        let offset = text
+            .char_indices()
+            .nth((self.cursor.0 as usize).saturating_sub(1))
+            .map_or(text.len(), |(i, _)| i);
+
+        if let Some(m) = search.find(line, offset, search.backwards != reverse) {
+            let text = content.lines().nth(m.line).unwrap_or_default();
+            let col = text[..m.start].chars().count() + 1;
+            self.move_to((col as u16, m.line as u16));
         }
     }
 
+    pub fn get_scroll(&self) -> (u16, u16) {
+        self.scroll
+    }
+
+    /// Scroll to (line, column), the next `update_scroll` brings the cursor back into view
+    pub fn set_scroll(&mut self, scroll: (u16, u16)) {
+        self.scroll = scroll;
+    }
+
     pub fn get_cursor(&self) -> (u16, u16) {
         self.cursor
     }
 
-    // TODO: constrain cursor to content but preserve column like vim
     pub fn move_cursor(&mut self, m: Direction) {
-        let line_len = match self.content {
-            Some(ref c) => c
-                .lines()
-                .nth(self.cursor.1 as usize)
-                .unwrap_or("")
-                .chars()
-                .count() as u16,
-            None => return,
+        let (col, line) = self.cursor;
+        let line = match m {
+            Direction::Up => line.saturating_sub(1),
+            Direction::Down => line.saturating_add(1),
+            Direction::Left => return self.move_to((col.saturating_sub(1), line)),
+            Direction::Right => return self.move_to((col.saturating_add(1), line)),
         };
-
-        match m {
-            Direction::Up => {
-                self.cursor.1 = match self.cursor.1 {
-                    0 => 0,
-                    x => x - 1,
-                };
-            }
-            Direction::Down => {
-                self.cursor.1 += 1;
-            }
-            Direction::Left => {
-                self.cursor.0 = match self.cursor.0 {
-                    0 => 0,
-                    x => {
-                        let line_len = match line_len {
-                            0 => 1,
-                            x => x,
-                        };
-                        std::cmp::min(x, line_len) - 1
-                    }
-                };
-            }
-            Direction::Right => {
-                self.cursor.0 = match line_len {
-                    0 => 0,
-                    x => std::cmp::min(x, self.cursor.0) + 1,
-                }
-            }
-        }
+        let goal_col = self.goal_col;
+        self.move_to((goal_col, line));
+        self.goal_col = goal_col;
     }
 
     pub fn update_scroll(&mut self, container: &ratatui::prelude::Rect) {
@@ -194,34 +370,41 @@ impl SourceView {
 
         let padding = self.line_padding as u16;
         let cols_in_view = container.width - 2 - padding;
-        if self.cursor.0 >= self.scroll.1 + cols_in_view {
-            self.scroll.1 = self.cursor.0 - cols_in_view + 1;
-        } else if self.cursor.0 < self.scroll.1 {
-            self.scroll.1 = self.cursor.0;
+        let (start, width) = self.cursor_cells();
+        if start + width > self.scroll.1 + cols_in_view {
+            self.scroll.1 = start + width - cols_in_view;
+        } else if start < self.scroll.1 {
+            self.scroll.1 = start;
         }
     }
 
-    pub fn global_cursor(&self, container: &ratatui::prelude::Rect) -> (u16, u16) {
-        let padding = self.line_padding as u16;
-        if self.content.is_none() {
-            return (padding + 1, 1);
-        }
-        let line_len = self
+    /// The screen columns under the cursor as (start, width), with the cursor kept on its line
+    fn cursor_cells(&self) -> (u16, u16) {
+        let line = self
             .content
             .as_ref()
-            .unwrap()
-            .lines()
-            .nth(self.cursor.1 as usize)
-            .unwrap_or("")
+            .and_then(|c| c.lines().nth(self.cursor.1 as usize))
+            .unwrap_or("");
+        let col = (self.cursor.0 as usize).clamp(1, line.chars().count().max(1));
+        let start = display_col(line, col, self.tab_width);
+        let width = line
             .chars()
-            .count() as u16;
-        let line_bounded = std::cmp::min(self.cursor.0, line_len);
-        let window_bounded = std::cmp::min(line_bounded, container.width - 2 - padding);
+            .nth(col - 1)
+            .map_or(1, |c| char_width(c, start, self.tab_width).max(1));
 
-        // Stay out of gutter
-        let bounded_cursor = std::cmp::max(window_bounded, 1);
+        (start as u16, width as u16)
+    }
 
-        let x = padding + container.x + bounded_cursor - self.scroll.1;
+    pub fn global_cursor(&self, container: &ratatui::prelude::Rect) -> (u16, u16) {
+        let padding = self.line_padding as u16;
+        if self.content.is_none() {
+            return (padding + 1, 1);
+        }
+        let (start, _) = self.cursor_cells();
+        let x = std::cmp::min(
+            (container.x + 1 + padding + start).saturating_sub(self.scroll.1),
+            container.x + container.width - 2,
+        );
 
         let y = std::cmp::min(
             self.cursor.1 + container.y + 1 - self.scroll.0,
@@ -231,3 +414,115 @@ impl SourceView {
         (x, y)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(content: &str) -> SourceView {
+        let mut sv = SourceView::new();
+        sv.content = Some(String::from(content));
+        sv
+    }
+
+    #[test]
+    fn test_search_wraps_around() {
+        let mut sv = view("int a;\nint *b = &a;\nreturn a;");
+        sv.search(r"\ba\b", false).unwrap();
+        assert_eq!(sv.get_cursor(), (5, 0));
+        sv.search_next(false);
+        assert_eq!(sv.get_cursor(), (11, 1));
+        sv.search_next(false);
+        assert_eq!(sv.get_cursor(), (8, 2));
+        sv.search_next(false);
+        assert_eq!(sv.get_cursor(), (5, 0));
+        sv.search_next(true);
+        assert_eq!(sv.get_cursor(), (8, 2));
+        assert_eq!(sv.search.as_ref().unwrap().status(), "[3/3] /\\ba\\b");
+    }
+
+    #[test]
+    fn test_search_backwards() {
+        let mut sv = view("a\nb a\na");
+        sv.move_to((1, 1));
+        sv.search("a", true).unwrap();
+        assert_eq!(sv.get_cursor(), (1, 0));
+        // `n` keeps the direction of `\`
+        sv.search_next(false);
+        assert_eq!(sv.get_cursor(), (1, 2));
+        sv.search_next(true);
+        assert_eq!(sv.get_cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_search_multibyte() {
+        let mut sv = view("/* é */ x");
+        sv.search("x", false).unwrap();
+        assert_eq!(sv.get_cursor(), (9, 0));
+        assert_eq!(sv.search.as_ref().unwrap().matches_on(0)[0].start, 9);
+    }
+
+    #[test]
+    fn test_display_col() {
+        assert_eq!(display_col("int a;", 5, 8), 4);
+        assert_eq!(display_col("\tx", 2, 8), 8);
+        assert_eq!(display_col("ab\tx", 4, 4), 4);
+        assert_eq!(display_col("漢字 x", 4, 8), 5);
+        // Past the end of the line
+        assert_eq!(display_col("\t", 4, 8), 10);
+        assert_eq!(expand_tabs("a\tb\t", 4), "a   b   ");
+    }
+
+    #[test]
+    fn test_cursor_cells() {
+        let mut sv = view("\tint 漢字;\n");
+        let area = ratatui::prelude::Rect::new(0, 0, 19, 10);
+        sv.get_widget();
+
+        sv.move_to((1, 0));
+        assert_eq!(sv.cursor_cells(), (0, 8));
+        sv.move_to((7, 0));
+        assert_eq!(sv.cursor_cells(), (14, 2));
+        // The border and the line number come first
+        assert_eq!(sv.global_cursor(&area), (17, 1));
+
+        // Scrolled so all of `字` is in view
+        sv.update_scroll(&area);
+        assert_eq!(sv.get_scroll(), (0, 1));
+        assert_eq!(sv.global_cursor(&area), (16, 1));
+
+        // Kept on the line
+        sv.move_to((20, 0));
+        assert_eq!(sv.get_cursor(), (8, 0));
+        assert_eq!(sv.cursor_cells(), (16, 1));
+    }
+
+    #[test]
+    fn test_move_cursor() {
+        let mut sv = view("int a;\n\nreturn a;");
+        sv.move_cursor(Direction::Left);
+        assert_eq!(sv.get_cursor(), (1, 0));
+        sv.move_to((6, 0));
+        sv.move_cursor(Direction::Right);
+        assert_eq!(sv.get_cursor(), (6, 0));
+        // The empty line only has column 1, the column comes back on the next line
+        sv.move_cursor(Direction::Down);
+        assert_eq!(sv.get_cursor(), (1, 1));
+        sv.move_cursor(Direction::Down);
+        assert_eq!(sv.get_cursor(), (6, 2));
+        sv.move_to_end();
+        sv.move_cursor(Direction::Down);
+        assert_eq!(sv.get_cursor(), (1, 2));
+        sv.move_to((0, 0));
+        assert_eq!(sv.get_cursor(), (1, 0));
+    }
+
+    #[test]
+    fn test_search_errors() {
+        let mut sv = view("a");
+        assert!(sv.search("(", false).is_err());
+        sv.search("z", false).unwrap();
+        assert_eq!(sv.get_cursor(), (1, 0));
+        assert_eq!(sv.search.as_ref().unwrap().status(), "no matches /z");
+    }
+}
@@ -0,0 +1,45 @@
+use std::{
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// A directory in the temp dir that is removed again when dropped, even if the test fails
+pub struct TempRoot {
+    path: PathBuf,
+}
+
+impl Deref for TempRoot {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A new directory holding `files` as (path, content), paths are relative to the directory
+pub fn write_root(files: &[(&str, &str)]) -> TempRoot {
+    // Tests run in parallel, so every root gets its own name
+    static ROOTS: AtomicUsize = AtomicUsize::new(0);
+    let n = ROOTS.fetch_add(1, Ordering::Relaxed);
+    let root = TempRoot {
+        path: std::env::temp_dir().join(format!("metavis-{}-{}", std::process::id(), n)),
+    };
+
+    std::fs::create_dir_all(&root.path).unwrap();
+    for (file, content) in files {
+        let path = root.path.join(file);
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        std::fs::write(path, content).unwrap();
+    }
+
+    root
+}
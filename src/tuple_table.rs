@@ -0,0 +1,304 @@
+use std::cmp::Ordering;
+
+use ratatui::{
+    prelude::{Constraint, Rect},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{Block, Cell, Row, Table, TableState},
+    Frame,
+};
+
+use crate::{
+    list::List,
+    parsers::{Column, Field, Tuple},
+};
+
+const MIN_WIDTH: u16 = 3;
+const MAX_DEFAULT_WIDTH: u16 = 30;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SortBy {
+    /// Node id for node columns, numeric or lexicographic for other columns
+    Value,
+    /// Source file, line and column of the node in a node column
+    Location,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Sort {
+    pub column: usize,
+    pub by: SortBy,
+    pub descending: bool,
+}
+
+/// Table view of the tuples of the selected relation, one column per field
+pub struct TupleTable {
+    pub state: TableState,
+    /// The column that sorting and resizing apply to
+    pub column: usize,
+    pub sort: Option<Sort>,
+    widths: Vec<Constraint>,
+}
+
+impl TupleTable {
+    pub fn new() -> Self {
+        Self {
+            state: TableState::default(),
+            column: 0,
+            sort: None,
+            widths: vec![],
+        }
+    }
+
+    /// Size the columns after the content of `tuples` and forget the sort order
+    pub fn reset(&mut self, tuples: &List<Tuple>, columns: Option<&[Column]>) {
+        let n_columns = Self::n_columns(tuples, columns);
+        let mut widths = vec![MIN_WIDTH; n_columns];
+        if let Some(columns) = columns {
+            for (w, c) in widths.iter_mut().zip(columns) {
+                *w = (*w).max(c.name.chars().count() as u16 + 2);
+            }
+        }
+        for t in &tuples.items {
+            for (w, label) in widths.iter_mut().zip(t.labels()) {
+                *w = (*w).max(label.chars().count() as u16 + 2);
+            }
+        }
+
+        self.widths = widths
+            .into_iter()
+            .map(|w| Constraint::Length(w.min(MAX_DEFAULT_WIDTH)))
+            .collect();
+        // The last column shows the source files
+        self.widths.push(Constraint::Percentage(100));
+        self.column = 0;
+        self.sort = None;
+        self.state = TableState::default();
+    }
+
+    fn n_columns(tuples: &List<Tuple>, columns: Option<&[Column]>) -> usize {
+        let widest = tuples.items.iter().map(|t| t.fields.len()).max();
+        widest.unwrap_or(0).max(columns.map_or(0, |c| c.len()))
+    }
+
+    pub fn next_column(&mut self) {
+        if self.column + 2 < self.widths.len() {
+            self.column += 1;
+        }
+    }
+
+    pub fn prev_column(&mut self) {
+        self.column = self.column.saturating_sub(1);
+    }
+
+    pub fn widen(&mut self) {
+        if let Some(Constraint::Length(w)) = self.widths.get_mut(self.column) {
+            *w += 1;
+        }
+    }
+
+    pub fn narrow(&mut self) {
+        if let Some(Constraint::Length(w)) = self.widths.get_mut(self.column) {
+            *w = (*w - 1).max(MIN_WIDTH);
+        }
+    }
+
+    /// Sort `tuples` on the current column, sorting again the same way reverses the order
+    pub fn sort(&mut self, tuples: &mut List<Tuple>, by: SortBy) {
+        let descending = match self.sort {
+            Some(s) if s.column == self.column && s.by == by => !s.descending,
+            _ => false,
+        };
+        let sort = Sort {
+            column: self.column,
+            by,
+            descending,
+        };
+
+        tuples.sort_by(|a, b| {
+            let ordering = match by {
+                SortBy::Value => {
+                    compare_values(a.fields.get(sort.column), b.fields.get(sort.column))
+                }
+                SortBy::Location => compare_locations(a, b, sort.column),
+            };
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+        self.sort = Some(sort);
+    }
+
+    pub fn render(
+        &mut self,
+        frame: &mut Frame,
+        area: Rect,
+        tuples: &List<Tuple>,
+        columns: Option<&[Column]>,
+        block: Block,
+        in_focus: bool,
+    ) {
+        let n_columns = self.widths.len().saturating_sub(1);
+        let mut header = (0..n_columns)
+            .map(|i| {
+                let mut name = match columns.and_then(|c| c.get(i)) {
+                    Some(c) => c.name.clone(),
+                    None => format!("#{}", i),
+                };
+                if let Some(sort) = self.sort.filter(|s| s.column == i) {
+                    name.push_str(match (sort.by, sort.descending) {
+                        (SortBy::Value, false) => " ▲",
+                        (SortBy::Value, true) => " ▼",
+                        (SortBy::Location, false) => " @▲",
+                        (SortBy::Location, true) => " @▼",
+                    });
+                }
+                let style = if in_focus && i == self.column {
+                    Style::new().bold().reversed()
+                } else {
+                    Style::new().bold()
+                };
+                Cell::from(name).style(style)
+            })
+            .collect::<Vec<_>>();
+        header.push(Cell::from("@").style(Style::new().bold()));
+
+        let rows = tuples
+            .items
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                let mut cells = t
+                    .cells()
+                    .into_iter()
+                    .map(|s| Cell::from(Line::from(s)))
+                    .collect::<Vec<_>>();
+                cells.resize(n_columns, Cell::from(""));
+                cells.push(Cell::from(t.source_files().join(" & ")));
+                let row = Row::new(cells);
+                if tuples.marked.contains(&i) {
+                    row.style(Style::default().bg(ratatui::style::Color::Green))
+                } else {
+                    row
+                }
+            })
+            .collect::<Vec<_>>();
+
+        let table = Table::new(rows)
+            .header(Row::new(header))
+            .widths(&self.widths)
+            .highlight_symbol(">")
+            .block(block);
+
+        self.state.select(tuples.state.selected());
+        frame.render_stateful_widget(table, area, &mut self.state);
+    }
+}
+
+fn compare_values(a: Option<&Field>, b: Option<&Field>) -> Ordering {
+    match (a, b) {
+        (Some(Field::Node(a)), Some(Field::Node(b))) => a.cmp(b),
+        (Some(Field::Value(a)), Some(Field::Value(b))) => {
+            match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+                (Ok(a), Ok(b)) => a.total_cmp(&b),
+                _ => a.cmp(b),
+            }
+        }
+        (Some(Field::Node(_)), Some(Field::Value(_))) => Ordering::Less,
+        (Some(Field::Value(_)), Some(Field::Node(_))) => Ordering::Greater,
+        // Missing columns last
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare_locations(a: &Tuple, b: &Tuple, column: usize) -> Ordering {
+    let key = |t: &Tuple| {
+        t.loc_of_column(column)
+            .map(|l| (l.source_file.clone(), l.start()))
+    };
+    match (key(a), key(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        // Unlocated nodes and plain values last
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => compare_values(a.fields.get(column), b.fields.get(column)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::Loc;
+
+    fn tuple(node: usize, value: &str, line: Option<usize>) -> Tuple {
+        let loc = line.map(|l| Loc {
+            start_line: l,
+            end_line: l,
+            source_file: String::from("a.c"),
+            ..Default::default()
+        });
+        Tuple::new(
+            String::from("R"),
+            vec![Field::Node(node), Field::Value(String::from(value))],
+            vec![loc],
+        )
+    }
+
+    fn nodes(tuples: &List<Tuple>) -> Vec<usize> {
+        tuples.items.iter().map(|t| t.nodes[0]).collect()
+    }
+
+    #[test]
+    fn test_sort() {
+        let mut tuples = List::new(vec![
+            tuple(3, "10", Some(1)),
+            tuple(1, "9", None),
+            tuple(2, "x", Some(5)),
+        ]);
+        let mut table = TupleTable::new();
+        table.reset(&tuples, None);
+
+        table.sort(&mut tuples, SortBy::Value);
+        assert_eq!(nodes(&tuples), vec![1, 2, 3]);
+        table.sort(&mut tuples, SortBy::Value);
+        assert_eq!(nodes(&tuples), vec![3, 2, 1]);
+
+        table.sort(&mut tuples, SortBy::Location);
+        assert_eq!(nodes(&tuples), vec![3, 2, 1]);
+
+        table.next_column();
+        table.sort(&mut tuples, SortBy::Value);
+        // Numbers compare as numbers, "x" is not a number
+        assert_eq!(nodes(&tuples), vec![1, 3, 2]);
+    }
+
+    #[test]
+    fn test_sort_keeps_selection() {
+        let mut tuples = List::new(vec![tuple(3, "", None), tuple(1, "", None)]);
+        tuples.confirm();
+        let mut table = TupleTable::new();
+        table.reset(&tuples, None);
+        table.sort(&mut tuples, SortBy::Value);
+        assert_eq!(tuples.selected().unwrap().nodes, vec![3]);
+        assert_eq!(tuples.state.selected(), Some(1));
+    }
+
+    #[test]
+    fn test_widths() {
+        let tuples = List::new(vec![tuple(12345, "", None)]);
+        let mut table = TupleTable::new();
+        table.reset(&tuples, None);
+        assert_eq!(table.widths[0], Constraint::Length(8));
+        table.widen();
+        assert_eq!(table.widths[0], Constraint::Length(9));
+        table.next_column();
+        table.narrow();
+        assert_eq!(table.widths[1], Constraint::Length(MIN_WIDTH));
+        table.next_column();
+        assert_eq!(table.column, 1);
+    }
+}
@@ -4,7 +4,10 @@ use ratatui::{
     Frame,
 };
 
-use crate::app_state;
+use crate::{
+    app_state,
+    keymap::{Action, Context},
+};
 
 fn get_border(title: &str, is_in_focus: bool) -> Block<'_> {
     let border = Block::new()
@@ -21,105 +24,418 @@ fn get_border(title: &str, is_in_focus: bool) -> Block<'_> {
 
 pub fn render(frame: &mut Frame, app_state: &mut app_state::AppState) {
     let area = frame.size();
-    let (left_pane, right_upper_pane, right_lower_pane, bottom_pane) = get_layout(&area);
+    let (mut left_pane, right_upper_pane, right_lower_pane, bottom_pane) = get_layout(&area);
+
+    if app_state.show_tree {
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints(vec![Constraint::Percentage(30), Constraint::Percentage(70)])
+            .split(left_pane);
+        left_pane = panes[1];
+
+        let tree_in_focus = app_state.focus == app_state::AppFocus::Tree;
+        let keymap = &app_state.keymap;
+        let mut title = keymap.title("directories", Action::ToggleTree);
+        if app_state.file_tree.hide_empty {
+            title.push_str(" (with tuples)");
+        }
+        let widget = app_state.file_tree.widget(&app_state.sv.name).block(
+            get_border(&title, tree_in_focus).title(
+                Title::from(keymap.hint(
+                    Context::Tree,
+                    &[
+                        (&[Action::Collapse, Action::Expand], "fold"),
+                        (&[Action::ToggleHideEmpty], "hide empty"),
+                    ],
+                ))
+                .alignment(Alignment::Right)
+                .position(block::Position::Bottom),
+            ),
+        );
+        frame.render_stateful_widget(widget, panes[0], &mut app_state.file_tree.state);
+    }
 
     app_state.sv.update_scroll(&left_pane);
     let source_widget = app_state.sv.get_widget();
 
     let source_name = &app_state.sv.name;
-    frame.render_widget(
-        source_widget.block(
-            get_border(source_name, app_state.focus == app_state::AppFocus::Source)
-                .title(Title::from(" [s]ource ").alignment(Alignment::Right))
-                .title(
-                    Title::from(" [:] goto line - [g] goto start - [G] goto end ")
-                        .alignment(Alignment::Right)
-                        .position(block::Position::Bottom),
-                ),
-        ),
-        left_pane,
-    );
+    let mut source_border = get_border(source_name, app_state.focus == app_state::AppFocus::Source)
+        .title(
+            Title::from(format!(
+                " {} ",
+                app_state.keymap.title("source", Action::FocusSource)
+            ))
+            .alignment(Alignment::Right),
+        )
+        .title(
+            Title::from(app_state.keymap.hint(
+                Context::Source,
+                &[
+                    (&[Action::GotoStart], "goto start"),
+                    (&[Action::GotoEnd], "goto end"),
+                    (&[Action::Search, Action::SearchBackward], "search"),
+                    (&[Action::SearchNext, Action::SearchPrev], "next"),
+                    (&[Action::OuterNode, Action::InnerNode], "node"),
+                ],
+            ))
+            .alignment(Alignment::Right)
+            .position(block::Position::Bottom),
+        );
+    if let Some(search) = &app_state.sv.search {
+        source_border = source_border.title(
+            Title::from(format!(" {} ", search.status()))
+                .alignment(Alignment::Left)
+                .position(block::Position::Bottom),
+        );
+    }
+    frame.render_widget(source_widget.block(source_border), left_pane);
     let cursor = app_state.sv.global_cursor(&left_pane);
     frame.set_cursor(cursor.0, cursor.1);
 
+    let relations_title = app_state.keymap.title("relations", Action::FocusRelations);
     let widget = app_state.relations.widget().block(
         get_border(
-            "[r]elations",
+            &relations_title,
             app_state.focus == app_state::AppFocus::Relations,
         )
         .title(
-            Title::from(" [enter] select ")
-                .alignment(Alignment::Right)
-                .position(block::Position::Bottom),
+            Title::from(
+                app_state
+                    .keymap
+                    .hint(Context::Relations, &[(&[Action::Select], "select")]),
+            )
+            .alignment(Alignment::Right)
+            .position(block::Position::Bottom),
         ),
     );
     frame.render_stateful_widget(widget, right_upper_pane, &mut app_state.relations.state);
 
-    let widget = app_state.tuples.widget().block(
-        get_border("[t]uples", app_state.focus == app_state::AppFocus::Tuples).title(
-            Title::from(" [left] previous - [right] next - [enter] select ")
+    let mut tuples_title = app_state.keymap.title("tuples", Action::FocusTuples);
+    if let Some(r) = app_state.relations.selected() {
+        tuples_title.push_str(&format!(": {}", r.name));
+    }
+    let tuples_hint = app_state.keymap.hint(
+        Context::Tuples,
+        &[
+            (&[Action::PrevNode, Action::NextNode], "node"),
+            (&[Action::PrevColumn, Action::NextColumn], "column"),
+            (&[Action::SortByValue, Action::SortByLocation], "sort"),
+            (&[Action::Narrow, Action::Widen], "width"),
+            (&[Action::Filter], "filter"),
+            (&[Action::Select], "select"),
+        ],
+    );
+    if let Some(filter) = &app_state.filter {
+        tuples_title.push_str(&format!(
+            " [/{}] {}/{}",
+            filter.source,
+            app_state.tuples.items.len(),
+            app_state.tuples_total
+        ));
+    }
+    let columns = app_state
+        .relations
+        .selected()
+        .and_then(|r| app_state.metainfo.get_columns(&r.name));
+    let tuples_in_focus = app_state.focus == app_state::AppFocus::Tuples;
+    app_state.tuple_table.render(
+        frame,
+        right_lower_pane,
+        &app_state.tuples,
+        columns,
+        get_border(&tuples_title, tuples_in_focus).title(
+            Title::from(tuples_hint)
                 .alignment(Alignment::Right)
                 .position(block::Position::Bottom),
         ),
+        tuples_in_focus,
     );
-    frame.render_stateful_widget(widget, right_lower_pane, &mut app_state.tuples.state);
 
     if app_state.focus == app_state::AppFocus::FilePicker {
-        let popup_area = centered_rect(40, 40, area);
+        let popup_area = centered_rect(60, 50, area);
         frame.render_widget(Clear, popup_area);
-        frame.render_stateful_widget(
-            app_state.files.widget().block(get_border(
-                "files",
-                app_state.focus == app_state::AppFocus::FilePicker,
+        let relation = app_state.relations.selected().map(|r| r.name.clone());
+        app_state.file_picker.render(
+            frame,
+            popup_area,
+            &app_state.metainfo,
+            relation.as_deref(),
+            get_border("files", true).title(
+                Title::from(format!(
+                    " type to filter -{}",
+                    app_state.keymap.hint(
+                        Context::Prompt,
+                        &[
+                            (&[Action::Up, Action::Down], "select"),
+                            (&[Action::Select], "open"),
+                            (&[Action::Close], "close"),
+                        ],
+                    )
+                ))
+                .alignment(Alignment::Right)
+                .position(block::Position::Bottom),
+            ),
+        );
+    }
+    if app_state.focus == app_state::AppFocus::CommandLine {
+        let popup_area = centered_line(50, area);
+        frame.render_widget(Clear, popup_area);
+        let bottom = match app_state.completion {
+            Some(i) => completions_line(&app_state.completions, i),
+            None => Line::from(format!(
+                " 42:7 - node 46 - rel - file - filter - export - reload -{}",
+                app_state
+                    .keymap
+                    .hint(Context::Prompt, &[(&[Action::Complete], "complete")])
             )),
+        };
+        let mut border = get_border("command:", true).title(
+            Title::from(bottom)
+                .alignment(Alignment::Right)
+                .position(block::Position::Bottom),
+        );
+        if let Some(e) = &app_state.input_error {
+            border = border.title(
+                Title::from(Span::styled(format!(" {} ", e), Style::new().light_red()))
+                    .alignment(Alignment::Right),
+            );
+        }
+        frame.render_widget(
+            Paragraph::new(app_state.input_buffer.as_str()).block(border),
             popup_area,
-            &mut app_state.files.state,
         );
     }
-    if app_state.focus == app_state::AppFocus::LinePicker {
-        let popup_area = centered_rect(10, 5, area);
+
+    if app_state.focus == app_state::AppFocus::FilterPrompt {
+        let popup_area = centered_rect(50, 5, area);
         frame.render_widget(Clear, popup_area);
+        let mut border = get_border("filter tuples:", true).title(
+            Title::from(" node:14,46 - file:*.c - line:10-20 - name=value - text ")
+                .alignment(Alignment::Right)
+                .position(block::Position::Bottom),
+        );
+        if let Some(e) = &app_state.input_error {
+            border = border.title(
+                Title::from(Span::styled(format!(" {} ", e), Style::new().light_red()))
+                    .alignment(Alignment::Right),
+            );
+        }
         frame.render_widget(
-            Paragraph::new(app_state.input_buffer.as_str()).block(get_border(
-                "goto line:",
-                app_state.focus == app_state::AppFocus::LinePicker,
-            )),
+            Paragraph::new(app_state.input_buffer.as_str()).block(border),
             popup_area,
         );
     }
 
-    let tuples = if app_state.sv.content.is_some() {
-        app_state.get_current_tuples()
-    } else {
-        vec![]
-    };
+    if app_state.focus == app_state::AppFocus::SearchPrompt {
+        let popup_area = centered_rect(50, 5, area);
+        frame.render_widget(Clear, popup_area);
+        let title = if app_state.search_backwards {
+            "search backwards (regex):"
+        } else {
+            "search (regex):"
+        };
+        let mut border = get_border(title, true);
+        if let Some(e) = &app_state.input_error {
+            border = border.title(
+                Title::from(Span::styled(format!(" {} ", e), Style::new().light_red()))
+                    .alignment(Alignment::Right),
+            );
+        }
+        frame.render_widget(
+            Paragraph::new(app_state.input_buffer.as_str()).block(border),
+            popup_area,
+        );
+    }
 
-    frame.render_widget(
-        Paragraph::new(
-            tuples
-                .iter()
-                .map(|d| {
-                    format!(
-                        "{}: ({})",
-                        d.name,
-                        d.nodes
-                            .iter()
-                            .map(|n| n.to_string())
-                            .collect::<Vec<_>>()
-                            .join(",")
+    if app_state.focus == app_state::AppFocus::Problems {
+        let popup_area = centered_rect(60, 50, area);
+        frame.render_widget(Clear, popup_area);
+        frame.render_stateful_widget(
+            app_state.problems.widget().block(
+                get_border(
+                    &format!("problems ({})", app_state.problems.items.len()),
+                    app_state.focus == app_state::AppFocus::Problems,
+                )
+                .title(
+                    Title::from(
+                        app_state
+                            .keymap
+                            .hint(Context::Global, &[(&[Action::Close], "close")]),
                     )
-                })
-                .collect::<Vec<_>>()
-                .join(", "),
-        )
-        .wrap(Wrap { trim: false })
-        .block(
-            get_border("information", false).title(
-                Title::from(" [tab] cycle focus - [f] file picker - [q] exit ")
                     .alignment(Alignment::Right)
                     .position(block::Position::Bottom),
+                ),
             ),
-        ),
+            popup_area,
+            &mut app_state.problems.state,
+        );
+    }
+
+    if app_state.focus == app_state::AppFocus::Help {
+        let popup_area = centered_rect(60, 70, area);
+        frame.render_widget(Clear, popup_area);
+
+        let mut lines = vec![];
+        for (context, bindings) in app_state.keymap.help(app_state.help_for.context()) {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+            lines.push(Line::styled(context.name(), Style::new().bold()));
+            let width = bindings.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+            for (keys, description) in bindings {
+                lines.push(Line::from(vec![
+                    Span::styled(format!("  {:<width$}  ", keys), Style::new().light_green()),
+                    Span::raw(description),
+                ]));
+            }
+        }
+        // Stop scrolling once the last line is at the bottom
+        let height = popup_area.height.saturating_sub(2);
+        let max_scroll = (lines.len() as u16).saturating_sub(height);
+        app_state.help_scroll = app_state.help_scroll.min(max_scroll);
+
+        let hint = app_state
+            .keymap
+            .hint(Context::Help, &[(&[Action::Down, Action::Up], "scroll")]);
+        let close = app_state
+            .keymap
+            .hint(Context::Global, &[(&[Action::Close], "close")]);
+        frame.render_widget(
+            Paragraph::new(lines)
+                .scroll((app_state.help_scroll, 0))
+                .block(
+                    get_border("help", true).title(
+                        Title::from(format!("{}- {}", hint, close.trim_start()))
+                            .alignment(Alignment::Right)
+                            .position(block::Position::Bottom),
+                    ),
+                ),
+            popup_area,
+        );
+    }
+
+    if let (app_state::AppFocus::Inspector, Some(inspector)) =
+        (app_state.focus, &mut app_state.inspector)
+    {
+        let popup_area = centered_rect(60, 60, area);
+        frame.render_widget(Clear, popup_area);
+
+        let hint = app_state.keymap.hint(
+            Context::Inspector,
+            &[
+                (&[Action::Down, Action::Up], "tuple"),
+                (&[Action::Select], "show"),
+            ],
+        );
+        let close = app_state
+            .keymap
+            .hint(Context::Global, &[(&[Action::Close], "close")]);
+        let border = get_border("nodes under the cursor", true).title(
+            Title::from(format!("{}- {}", hint, close.trim_start()))
+                .alignment(Alignment::Right)
+                .position(block::Position::Bottom),
+        );
+        if inspector.is_empty() {
+            frame.render_widget(
+                Paragraph::new("no node under the cursor").block(border),
+                popup_area,
+            );
+        } else {
+            frame.render_stateful_widget(
+                inspector.widget().block(border),
+                popup_area,
+                &mut inspector.state,
+            );
+        }
+    }
+
+    let tuples = if app_state.sv.content.is_some() {
+        app_state.current_tuples.as_slice()
+    } else {
+        &[]
+    };
+
+    let mut information_border = get_border("information", false).title(
+        Title::from(app_state.keymap.hint(
+            Context::Global,
+            &[
+                (&[Action::FocusNext], "cycle focus"),
+                (&[Action::FilePicker], "file picker"),
+                (&[Action::ToggleTree], "directories"),
+                (&[Action::Inspect], "inspect"),
+                (&[Action::Help], "help"),
+                (&[Action::Quit], "exit"),
+            ],
+        ))
+        .alignment(Alignment::Right)
+        .position(block::Position::Bottom),
+    );
+    if !app_state.problems.items.is_empty() {
+        information_border = information_border.title(
+            Title::from(Span::styled(
+                format!(
+                    " {}: {} ",
+                    app_state.keymap.title("problems", Action::Problems),
+                    app_state.problems.items.len()
+                ),
+                Style::new().light_red(),
+            ))
+            .alignment(Alignment::Right),
+        );
+    }
+
+    let mut information = vec![];
+    if let Some(selected) = app_state.tuples.selected() {
+        let unlocated = selected
+            .unlocated()
+            .map(|n| n.to_string())
+            .collect::<Vec<_>>();
+        if !unlocated.is_empty() {
+            information.push(Line::styled(
+                format!(
+                    "{}: {}: node(s) {} have no source location",
+                    selected.name,
+                    selected
+                        .named_row()
+                        .into_iter()
+                        .map(|s| s.content)
+                        .collect::<String>(),
+                    unlocated.join(",")
+                ),
+                Style::new().yellow(),
+            ));
+        }
+    }
+    // The nodes under the cursor from the outermost one in
+    if !app_state.current_nodes.is_empty() {
+        let mut breadcrumb = vec![Span::raw("nodes: ")];
+        for (depth, node) in app_state.current_nodes.iter().enumerate().rev() {
+            let style = if depth == app_state.node_depth {
+                Style::new().reversed()
+            } else {
+                Style::new()
+            };
+            breadcrumb.push(Span::styled(app_state.metainfo.label(*node), style));
+            if depth > 0 {
+                breadcrumb.push(Span::raw(" > "));
+            }
+        }
+        information.push(Line::from(breadcrumb));
+    }
+    let mut current = vec![];
+    for (i, d) in tuples.iter().enumerate() {
+        if i > 0 {
+            current.push(Span::raw(", "));
+        }
+        current.push(Span::raw(format!("{}: ", d.name)));
+        current.extend(d.named_row());
+    }
+    information.push(Line::from(current));
+
+    frame.render_widget(
+        Paragraph::new(information)
+            .wrap(Wrap { trim: false })
+            .block(information_border),
         bottom_pane,
     );
 }
@@ -148,6 +464,34 @@ fn get_layout(
     (left_pane, right_upper_pane, right_lower_pane, bottom_pane)
 }
 
+/// A page of `completions` with the `current` one highlighted, only the part being completed
+fn completions_line(completions: &[String], current: usize) -> Line<'static> {
+    const PAGE: usize = 6;
+    let start = current / PAGE * PAGE;
+    let mut spans = vec![Span::raw(" ")];
+    for (i, c) in completions.iter().enumerate().skip(start).take(PAGE) {
+        let text = c.split_once(' ').map_or(c.as_str(), |(_, arg)| arg);
+        let text = if text.is_empty() { c.trim_end() } else { text };
+        let style = if i == current {
+            Style::new().reversed()
+        } else {
+            Style::new()
+        };
+        spans.push(Span::styled(text.to_owned(), style));
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::raw(format!("{}/{} ", current + 1, completions.len())));
+    Line::from(spans)
+}
+
+/// A centered rect of `percent_x` of the width of `r`, high enough for one line of text
+fn centered_line(percent_x: u16, r: Rect) -> Rect {
+    let mut area = centered_rect(percent_x, 0, r);
+    area.height = 3.min(r.height);
+    area.y = r.y + (r.height - area.height) / 2;
+    area
+}
+
 /// helper function to create a centered rect using up certain percentage of the available rect `r`
 fn centered_rect(percent_x: u16, percent_y: u16, r: Rect) -> Rect {
     // Cut the given rectangle into three vertical pieces